@@ -1,45 +1,145 @@
 /// Copyright (c) 2020, Shoyo Inokuchi
 use crate::tiles::TileColor;
-use std::collections::HashMap;
-
-pub struct Colors;
-
-impl Colors {
-    pub fn all() -> HashMap<TileColor, bool> {
-        let mut map = HashMap::new();
-        map.insert(TileColor::Black, true);
-        map.insert(TileColor::Red, true);
-        map.insert(TileColor::Blue, true);
-        map.insert(TileColor::Orange, true);
-        map
-    }
-
-    pub fn none() -> HashMap<TileColor, bool> {
-        let mut map = HashMap::new();
-        map.insert(TileColor::Black, false);
-        map.insert(TileColor::Red, false);
-        map.insert(TileColor::Blue, false);
-        map.insert(TileColor::Orange, false);
-        map
-    }
-
-    pub fn only(color: TileColor) -> HashMap<TileColor, bool> {
-        let mut map = HashMap::new();
-        map.insert(TileColor::Black, false);
-        map.insert(TileColor::Red, false);
-        map.insert(TileColor::Blue, false);
-        map.insert(TileColor::Orange, false);
-        map.insert(color, true);
-        map
-    }
-
-    pub fn except(color: TileColor) -> HashMap<TileColor, bool> {
-        let mut map = HashMap::new();
-        map.insert(TileColor::Black, true);
-        map.insert(TileColor::Red, true);
-        map.insert(TileColor::Blue, true);
-        map.insert(TileColor::Orange, true);
-        map.insert(color, false);
-        map
+use std::iter::FromIterator;
+
+/// The colors in the order they are stored and listed.
+const COLORS: [TileColor; 4] = [
+    TileColor::Black,
+    TileColor::Red,
+    TileColor::Blue,
+    TileColor::Orange,
+];
+
+/// A set of tile colors, stored as one bit per color.
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub struct ColorSet {
+    bits: u8,
+}
+
+impl ColorSet {
+    pub fn all() -> Self {
+        Self { bits: 0b1111 }
+    }
+
+    pub fn none() -> Self {
+        Self { bits: 0 }
+    }
+
+    pub fn only(color: TileColor) -> Self {
+        Self { bits: _bit(color) }
+    }
+
+    pub fn except(color: TileColor) -> Self {
+        Self::only(color).complement()
+    }
+
+    pub fn contains(self, color: TileColor) -> bool {
+        self.bits & _bit(color) != 0
+    }
+
+    pub fn insert(&mut self, color: TileColor) {
+        self.bits |= _bit(color);
+    }
+
+    pub fn remove(&mut self, color: TileColor) {
+        self.bits &= !_bit(color);
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            bits: self.bits | other.bits,
+        }
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self {
+            bits: self.bits & other.bits,
+        }
+    }
+
+    /// Return the colors missing from the set.
+    pub fn complement(self) -> Self {
+        Self {
+            bits: !self.bits & Self::all().bits,
+        }
+    }
+
+    pub fn len(self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.bits == 0
+    }
+
+    /// Return the colors in the set, in the order black, red, blue and orange.
+    pub fn iter(self) -> impl Iterator<Item = TileColor> {
+        COLORS
+            .iter()
+            .copied()
+            .filter(move |&color| self.contains(color))
+    }
+}
+
+impl FromIterator<TileColor> for ColorSet {
+    fn from_iter<I: IntoIterator<Item = TileColor>>(colors: I) -> Self {
+        let mut set = Self::none();
+        for color in colors {
+            set.insert(color);
+        }
+        set
+    }
+}
+
+/// Return the bit that stands for the color in a `ColorSet`.
+fn _bit(color: TileColor) -> u8 {
+    match color {
+        TileColor::Black => 1,
+        TileColor::Red => 1 << 1,
+        TileColor::Blue => 1 << 2,
+        TileColor::Orange => 1 << 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_and_except() {
+        let red = ColorSet::only(TileColor::Red);
+        assert!(red.contains(TileColor::Red));
+        assert!(!red.contains(TileColor::Blue));
+        assert_eq!(red.len(), 1);
+        let others = ColorSet::except(TileColor::Red);
+        assert_eq!(others, red.complement());
+        assert_eq!(others.len(), 3);
+        assert_eq!(red.union(others), ColorSet::all());
+        assert!(red.intersection(others).is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut set = ColorSet::none();
+        set.insert(TileColor::Orange);
+        set.insert(TileColor::Black);
+        set.insert(TileColor::Orange);
+        assert_eq!(set.len(), 2);
+        set.remove(TileColor::Black);
+        set.remove(TileColor::Blue);
+        assert_eq!(set, ColorSet::only(TileColor::Orange));
+        assert_eq!(ColorSet::all().complement(), ColorSet::none());
+    }
+
+    #[test]
+    fn test_iter() {
+        let set: ColorSet = vec![TileColor::Orange, TileColor::Black, TileColor::Blue]
+            .into_iter()
+            .collect();
+        let colors: Vec<TileColor> = set.iter().collect();
+        assert_eq!(
+            colors,
+            vec![TileColor::Black, TileColor::Blue, TileColor::Orange]
+        );
     }
 }
@@ -1,4 +1,8 @@
 pub mod colors;
 pub mod parser;
+pub mod position;
+pub mod record;
+pub mod rules;
+pub mod script;
 pub mod solve;
 pub mod tiles;
@@ -1,31 +1,36 @@
 /// Copyright (c) 2020, Shoyo Inokuchi
-use rummikub::parser::is_valid_set;
-use rummikub::tiles::deserialize_set;
+use rummikub::parser::validate_declared_set;
+use rummikub::rules::RuleSet;
+use rummikub::tiles::deserialize_declared_set;
 use std::io::{self, Write};
 
 fn main() {
     println!("Input a tile sequence:");
-    let mut set = Vec::new();
     loop {
         print!("> ");
         io::stdout().flush().unwrap();
 
         let mut buf = String::new();
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut buf)
             .expect("Failed to read from stdin");
+        if read == 0 {
+            println!();
+            break;
+        }
 
-        match deserialize_set(buf.trim()) {
-            Ok(s) => set = s,
+        let set = match deserialize_declared_set(&buf) {
+            Ok(s) if s.tiles.is_empty() => continue,
+            Ok(s) => s,
             Err(e) => {
                 println!("{}", e);
                 continue;
             }
-        }
+        };
 
-        match is_valid_set(&set) {
-            true => println!("Valid set."),
-            false => println!("Invalid set."),
+        match validate_declared_set(&set, &RuleSet::special_jokers()) {
+            Ok(kind) => println!("Valid set: {}.", kind),
+            Err(e) => println!("Invalid set: {}.", e),
         }
     }
 }
@@ -1,8 +1,202 @@
 /// Copyright (c) 2020, Shoyo Inokuchi
-use crate::colors::Colors;
-use crate::tiles::{BasicTile, Joker, JokerVariant, Tile, TileColor, TileValue};
-use std::collections::HashMap;
+use crate::colors::ColorSet;
+use crate::rules::RuleSet;
+use crate::tiles::{BasicTile, Declaration, DeclaredSet, JokerVariant, Tile, TileColor, TileValue};
+use std::error::Error;
+use std::fmt;
+use std::iter;
+
+/// The kind of set recognised by the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetKind {
+    /// A sequence of tiles with consecutive values.
+    Run {
+        /// `colors` stores the color of each stretch of the run from left to right. A run only has
+        /// more than one stretch if it contains color-change jokers. The color of a stretch made
+        /// up solely of jokers is unknown.
+        colors: Vec<Option<TileColor>>,
+
+        /// `start` and `end` store the values at either end of the run. Both are unknown when the
+        /// run does not contain a basic tile.
+        start: Option<TileValue>,
+        end: Option<TileValue>,
+    },
+    /// A sequence of tiles sharing the same value in different colors.
+    Group {
+        value: TileValue,
+
+        /// `colors` stores the colors of the basic tiles in the group, in order of appearance.
+        colors: Vec<TileColor>,
+    },
+    /// A sequence that can be read both as a run and as a group.
+    /// Ex. J 5 J .. is either a run from 4 to 6 or a group of 5s
+    ///     J J J .. contains no basic tile at all
+    Undetermined,
+    /// A sequence that is symmetric across a mirror joker. The inner kind describes the tiles on
+    /// either side of the mirror.
+    Mirror(Box<SetKind>),
+}
+
+impl fmt::Display for SetKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SetKind::Run { colors, start, end } => {
+                match (start, end) {
+                    (Some(start), Some(end)) => write!(f, "run from {} to {}", start, end)?,
+                    _ => write!(f, "run")?,
+                }
+                write!(f, " in ")?;
+                for (i, color) in colors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " -> ")?;
+                    }
+                    match color {
+                        Some(color) => write!(f, "{}", color)?,
+                        None => write!(f, "ANY")?,
+                    }
+                }
+                Ok(())
+            }
+            SetKind::Group { value, colors } => {
+                write!(f, "group of {}s in ", value)?;
+                for (i, color) in colors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", color)?;
+                }
+                Ok(())
+            }
+            SetKind::Undetermined => write!(f, "undetermined set"),
+            SetKind::Mirror(half) => write!(f, "mirrored {}", half),
+        }
+    }
+}
+
+/// The reason a sequence of tiles is not a valid set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidReason {
+    /// The set contains fewer tiles than the rules allow, or no tiles at all.
+    TooShort,
+    /// A basic tile has a value outside of 1 to 13.
+    InvalidTileValue,
+    /// A run starts below 1 or ends above 13, or is longer than 13 tiles.
+    ValueOutOfRange,
+    /// A tile in a run is lower than its position in the run allows.
+    /// Ex. 9 8 7 .. or 5 J 6 ..
+    DescendingRun,
+    /// A tile in a run is higher than its position in the run allows.
+    /// Ex. 5 7 8 ..
+    GapInRun,
+    /// A tile in a run does not match the color of the tiles before it.
+    ColorMismatchInRun,
+    /// The tile following a color-change joker has the same color as the tiles before the joker.
+    MissingColorSwitch,
+    /// A tile in a group does not share the value of the group.
+    ValueMismatchInGroup,
+    /// A color appears more than once in a group.
+    ColorRepeatedInGroup,
+    /// A group contains more tiles than the rules allow.
+    GroupTooLarge,
+    /// A color-change joker appears in a group.
+    ColorChangeInGroup,
+    /// The tiles on either side of a mirror joker are not mirror images of each other.
+    AsymmetricMirror,
+    /// A mirror joker appears at either end of the set, or more than once.
+    MisplacedMirror,
+    /// Two color-change jokers are placed next to each other without the house rule allowing it.
+    AdjacentColorChanges,
+    /// The set contains a special joker that is not in play.
+    JokerNotAllowed,
+    /// The set contains more copies of a tile than the box.
+    TooManyCopies,
+    /// A joker cannot stand for the tiles it is declared to stand for.
+    DeclarationMismatch,
+}
+
+impl fmt::Display for InvalidReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvalidReason::TooShort => write!(f, "set has too few tiles"),
+            InvalidReason::InvalidTileValue => write!(f, "tile value is not between 1 and 13"),
+            InvalidReason::ValueOutOfRange => write!(f, "run goes below 1 or above 13"),
+            InvalidReason::DescendingRun => write!(f, "run does not ascend"),
+            InvalidReason::GapInRun => write!(f, "run skips a value"),
+            InvalidReason::ColorMismatchInRun => write!(f, "run changes color"),
+            InvalidReason::MissingColorSwitch => {
+                write!(f, "color-change joker is not followed by a new color")
+            }
+            InvalidReason::ValueMismatchInGroup => write!(f, "group mixes values"),
+            InvalidReason::ColorRepeatedInGroup => write!(f, "group repeats a color"),
+            InvalidReason::GroupTooLarge => write!(f, "group has too many tiles"),
+            InvalidReason::ColorChangeInGroup => write!(f, "color-change joker is in a group"),
+            InvalidReason::AsymmetricMirror => write!(f, "sides of the mirror do not match"),
+            InvalidReason::MisplacedMirror => write!(f, "mirror joker is misplaced"),
+            InvalidReason::AdjacentColorChanges => {
+                write!(f, "color-change jokers are next to each other")
+            }
+            InvalidReason::JokerNotAllowed => write!(f, "joker is not in play"),
+            InvalidReason::TooManyCopies => write!(f, "tile has run out in the box"),
+            InvalidReason::DeclarationMismatch => write!(f, "joker does not fit its declaration"),
+        }
+    }
+}
+
+/// The reason a set is invalid, along with the index of the tile where the parser rejected it.
+/// For sets that are too short, `index` is the length of the set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidSet {
+    pub reason: InvalidReason,
+    pub index: usize,
+}
+
+impl fmt::Display for InvalidSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            InvalidReason::TooShort => write!(f, "{}", self.reason),
+            _ => write!(f, "{} (tile {})", self.reason, self.index + 1),
+        }
+    }
+}
+
+impl Error for InvalidSet {}
+
+/// A tile that a joker stands for. The color or value is unknown when the set leaves it open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StandIn {
+    pub color: Option<TileColor>,
+    pub value: Option<TileValue>,
+}
+
+/// What a joker represents within a valid set.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JokerRole {
+    /// A single joker stands for one tile.
+    Single(StandIn),
+    /// A double joker stands for two adjacent tiles, from left to right.
+    Double(StandIn, StandIn),
+    /// A mirror joker reflects the tiles on one side of it onto the other. The tiles are listed
+    /// from the mirror outward, which reads the same on both sides.
+    Mirror(Vec<StandIn>),
+    /// A color-change joker stands for the tile of `value` at which the run switches from one
+    /// color to another, reading from left to right.
+    ColorChange {
+        value: Option<TileValue>,
+        from: Option<TileColor>,
+        to: Option<TileColor>,
+    },
+}
+
+/// A single position within a set. Double jokers occupy two positions.
+#[derive(Clone, Copy, PartialEq)]
+enum Unit {
+    Basic(TileColor, TileValue),
+    Joker,
+    ColorChange,
+}
 
+/// The state of the parser. No state holds anything on the heap, so that validating a set never
+/// allocates.
 enum Parsing {
     Run {
         /// `last_value` is unknown while the tiles encountered so far did not contain a basic tile, and
@@ -13,12 +207,16 @@ enum Parsing {
         last_value: Option<TileValue>,
 
         /// `allow` keeps track of which colors are allowed for the current tile.
-        /// Typically, only one color maps to true while the rest map to false.
-        /// However, when a color-change joker is encountered, the map inverts and the
-        /// previous tile's color exclusively maps to false.
-        /// In the case that a color-change joker is encountered after another color-change joker
-        /// before encountering a basic tile, every color maps to true until a basic tile is encountered.
-        allow: HashMap<TileColor, bool>,
+        /// Typically, only one color is allowed.
+        /// However, when a color-change joker is encountered, the set inverts and only the
+        /// previous tile's color is left out.
+        /// In the case that a color-change joker is encountered before the color of the current
+        /// stretch is known, every color is allowed until a basic tile is encountered.
+        allow: ColorSet,
+
+        /// `color` stores the color of the current stretch of the run, which is unknown until a
+        /// basic tile is encountered in it. A new stretch begins after every color-change joker.
+        color: Option<TileColor>,
 
         /// `size` tracks the current length of the sequence.
         size: u8,
@@ -28,7 +226,7 @@ enum Parsing {
         value: TileValue,
 
         /// `allow` keeps track of which colors are still available for upcoming tiles.
-        allow: HashMap<TileColor, bool>,
+        allow: ColorSet,
 
         /// `size` tracks the current length of the sequence.
         size: u8,
@@ -47,1015 +245,2502 @@ enum Parsing {
     },
 }
 
-/// Given an ordered set of Rummikub tiles, return whether the set is valid.
-pub fn is_valid_set(set: &Vec<Tile>) -> bool {
-    if set.len() < 3 {
-        return false;
+/// Given an ordered set of Rummikub tiles, return whether the set is valid when playing with every
+/// special joker. Unlike `validate_set`, this never allocates.
+pub fn is_valid_set(set: &[Tile]) -> bool {
+    _is_valid(set, &RuleSet::special_jokers())
+}
+
+/// Given an ordered set of Rummikub tiles, return whether the set is valid under the given rules,
+/// without allocating.
+pub(crate) fn _is_valid(set: &[Tile], rules: &RuleSet) -> bool {
+    _validate(set, rules).is_ok()
+}
+
+/// Given an ordered set of Rummikub tiles, return the kind of set it forms under the given rules, or
+/// None if the set is invalid.
+pub fn classify_set(set: &[Tile], rules: &RuleSet) -> Option<SetKind> {
+    validate_set(set, rules).ok()
+}
+
+/// Given an ordered set of Rummikub tiles, return the kind of set it forms under the given rules, or
+/// the reason the set is invalid along with the index of the tile where it was rejected.
+pub fn validate_set(set: &[Tile], rules: &RuleSet) -> Result<SetKind, InvalidSet> {
+    let parsed = _validate(set, rules)?;
+    match _mirror_axis(set) {
+        Some(axis) => {
+            let half = _kind(parsed, _folded(set, axis), rules);
+            Ok(SetKind::Mirror(Box::new(half)))
+        }
+        None => Ok(_kind(parsed, _positions(set.iter().enumerate()), rules)),
+    }
+}
+
+/// Given an ordered set of Rummikub tiles along with the tiles some of its jokers are declared to
+/// stand for, return the kind of set the tiles form under the given rules, or the reason the set is
+/// invalid. A declaration that its joker cannot stand for, given the declarations to its left, is
+/// rejected at the index of the joker.
+/// Ex. 5 J=6 7       .. is valid
+///     5 J=8 7       .. is NOT valid
+///     7 J=U7 J=U7   .. is NOT valid, as the second joker repeats blue
+pub fn validate_declared_set(set: &DeclaredSet, rules: &RuleSet) -> Result<SetKind, InvalidSet> {
+    let kind = validate_set(&set.tiles, rules)?;
+    let mut declarations = set.declarations.clone();
+    declarations.sort_by_key(|&(index, _)| index);
+
+    // Replace the declared jokers by their tiles from left to right, checking the set every time.
+    let mut declared = set.tiles.clone();
+    let mut shift = 0;
+    let mut last = None;
+    for (index, declaration) in declarations {
+        let joker = match set.tiles.get(index) {
+            Some(Tile::Joker(j)) if last != Some(index) => j.variant,
+            _ => return _invalid(InvalidReason::DeclarationMismatch, index),
+        };
+        let tiles = match (joker, declaration) {
+            (JokerVariant::Single, Declaration::Single(tile)) => vec![tile],
+            (JokerVariant::Double, Declaration::Double(first, second)) => vec![first, second],
+            _ => return _invalid(InvalidReason::DeclarationMismatch, index),
+        };
+        let at = index + shift;
+        shift += tiles.len() - 1;
+        declared.splice(at..=at, tiles.into_iter().map(Tile::Basic));
+        if !_is_valid(&declared, rules) {
+            return _invalid(InvalidReason::DeclarationMismatch, index);
+        }
+        last = Some(index);
+    }
+    Ok(kind)
+}
+
+/// Check the set and return the state the parser ended in, folding the set across its mirror
+/// joker if it has one.
+fn _validate(set: &[Tile], rules: &RuleSet) -> Result<Parsing, InvalidSet> {
+    if set.len() < rules.min_set_size.max(1) as usize {
+        return _invalid(InvalidReason::TooShort, set.len());
+    }
+    _check_tiles(set, rules)?;
+    match _mirror_axis(set) {
+        Some(axis) => {
+            _check_mirror(set, axis)?;
+            _parse(_folded(set, axis), rules)
+        }
+        None => _parse(_positions(set.iter().enumerate()), rules),
+    }
+}
+
+/// Given an ordered set of Rummikub tiles, return what each joker in the set stands for under the
+/// given rules, paired with the index of the joker. Return the reason the set is invalid otherwise.
+pub fn resolve_jokers(
+    set: &[Tile],
+    rules: &RuleSet,
+) -> Result<Vec<(usize, JokerRole)>, InvalidSet> {
+    let kind = validate_set(set, rules)?;
+
+    // Record the positions occupied by each tile from left to right. Tiles on the right of a
+    // mirror occupy the positions of the folded half in reverse order.
+    let mut positions = vec![Vec::new(); set.len()];
+    let axis = _mirror_axis(set);
+    let (half, units) = match (&kind, axis) {
+        (SetKind::Mirror(half), Some(axis)) => {
+            let left = _expand(set.iter().enumerate().take(axis))?;
+            let right = _expand(set.iter().enumerate().skip(axis + 1).rev())?;
+            for (position, &(index, _)) in left.iter().enumerate() {
+                positions[index].push(position);
+            }
+            for (position, &(index, _)) in right.iter().enumerate() {
+                positions[index].insert(0, position);
+            }
+            (half.as_ref(), _reflect(set, axis)?)
+        }
+        _ => {
+            let units = _expand(set.iter().enumerate())?;
+            for (position, &(index, _)) in units.iter().enumerate() {
+                positions[index].push(position);
+            }
+            (&kind, units)
+        }
+    };
+
+    // Work out what every position of the (folded) set stands for, along with the color switch at
+    // every color-change joker.
+    let mut stand_ins = Vec::new();
+    let mut switches = Vec::new();
+    let mut stretch = 0;
+    for (position, &(_, unit)) in units.iter().enumerate() {
+        let stand_in = match (unit, half) {
+            (Unit::Basic(color, value), _) => StandIn {
+                color: Some(color),
+                value: Some(value),
+            },
+            (_, SetKind::Run { colors, start, .. }) => StandIn {
+                color: match unit {
+                    Unit::ColorChange => None,
+                    _ => colors[stretch],
+                },
+                value: start.and_then(|val| _offset(val, position as u8, rules)),
+            },
+            (_, SetKind::Group { value, colors }) => {
+                // The color of a joker is only known if it fills the last color of the group.
+                let missing = colors.iter().copied().collect::<ColorSet>().complement();
+                let jokers = units.iter().filter(|(_, u)| *u == Unit::Joker).count();
+                StandIn {
+                    color: if missing.len() == 1 && jokers == 1 {
+                        missing.iter().next()
+                    } else {
+                        None
+                    },
+                    value: Some(*value),
+                }
+            }
+            _ => StandIn {
+                color: None,
+                value: None,
+            },
+        };
+        stand_ins.push(stand_in);
+
+        match (unit, half) {
+            (Unit::ColorChange, SetKind::Run { colors, .. }) => {
+                switches.push((colors[stretch], colors[stretch + 1]));
+                stretch += 1;
+            }
+            _ => switches.push((None, None)),
+        }
+    }
+
+    let mut roles = Vec::new();
+    for (index, tile) in set.iter().enumerate() {
+        let joker = match tile {
+            Tile::Joker(j) => j,
+            Tile::Basic(_) => continue,
+        };
+        let covered = &positions[index];
+        let role = match joker.variant {
+            JokerVariant::Single => JokerRole::Single(stand_ins[covered[0]]),
+            JokerVariant::Double => JokerRole::Double(stand_ins[covered[0]], stand_ins[covered[1]]),
+            JokerVariant::Mirror => JokerRole::Mirror(stand_ins.iter().rev().cloned().collect()),
+            JokerVariant::ColorChange => {
+                // Reading from left to right, the right side of a mirror switches colors in the
+                // opposite direction.
+                let (from, to) = switches[covered[0]];
+                let (from, to) = match axis {
+                    Some(axis) if index > axis => (to, from),
+                    _ => (from, to),
+                };
+                JokerRole::ColorChange {
+                    value: stand_ins[covered[0]].value,
+                    from,
+                    to,
+                }
+            }
+        };
+        roles.push((index, role));
+    }
+    Ok(roles)
+}
+
+/// Given an ordered set of Rummikub tiles, return the points it is worth under the given rules, or
+/// the reason the set is invalid. Every joker is worth the tiles it stands for, and the tiles on
+/// either side of a mirror joker both count. Where the set leaves the values of its jokers open,
+/// it is worth the most points it could stand for.
+/// Ex. 6 DJ 9    .. is worth 30
+///     3 4 M 4 3 .. is worth 14
+///     8 J J     .. is worth 27, as a run from 8 to 10
+pub fn set_points(set: &[Tile], rules: &RuleSet) -> Result<u32, InvalidSet> {
+    let kind = validate_set(set, rules)?;
+    let (half, units, sides) = match (&kind, _mirror_axis(set)) {
+        (SetKind::Mirror(half), Some(axis)) => (half.as_ref(), _reflect(set, axis)?, 2),
+        _ => (&kind, _expand(set.iter().enumerate())?, 1),
+    };
+    let len = units.len() as u8;
+    let run = |start: TileValue| -> Option<u32> {
+        (0..len)
+            .map(|position| _offset(start, position, rules).map(u32::from))
+            .sum()
+    };
+    let group = |value: TileValue| value as u32 * len as u32;
+    let points = match *half {
+        SetKind::Run {
+            start: Some(start), ..
+        } => run(start).unwrap_or(0),
+        SetKind::Group { value, .. } => group(value),
+        _ => {
+            // Without a second basic tile, the set may be read as a run through its basic tile or
+            // as a group of its value, and without any basic tile, as a run up to 13 or a group
+            // of 13s.
+            let basic = units
+                .iter()
+                .enumerate()
+                .find_map(|(position, &(_, unit))| match unit {
+                    Unit::Basic(_, value) => Some((position as u8, value)),
+                    _ => None,
+                });
+            let (start, value) = match basic {
+                Some((position, value)) => (_offset_back(value, position, rules), value),
+                None => {
+                    let last = len.checked_sub(1);
+                    (last.and_then(|last| _offset_back(13, last, rules)), 13)
+                }
+            };
+            let group = match *half {
+                SetKind::Undetermined if len <= rules.max_group_size.min(4) => Some(group(value)),
+                _ => None,
+            };
+            start.and_then(run).max(group).unwrap_or(0)
+        }
+    };
+    Ok(points * sides)
+}
+
+/// Given an unordered collection of Rummikub tiles, return an ordering of the tiles that forms a
+/// valid set under the given rules, or None if there is no such ordering. Runs are laid out in
+/// ascending order, and tiles are otherwise placed lowest value first wherever possible.
+pub fn arrange_set(tiles: &[Tile], rules: &RuleSet) -> Option<Vec<Tile>> {
+    let mut found = Vec::new();
+    _arrange(tiles, rules, Some(1), &mut found);
+    found.pop()
+}
+
+/// Given an unordered collection of Rummikub tiles, return every distinct ordering of the tiles
+/// that forms a valid set under the given rules, in the same order of preference as `arrange_set`.
+pub fn arrangements(tiles: &[Tile], rules: &RuleSet) -> Vec<Vec<Tile>> {
+    let mut found = Vec::new();
+    _arrange(tiles, rules, None, &mut found);
+    found
+}
+
+/// Search for valid orderings of the given tiles, stopping once `limit` orderings are found.
+///
+/// Implementation:
+/// The tiles are sorted by value, and orderings are built one tile at a time by depth-first search.
+/// Copies of the same tile are interchangeable, so only the first unused copy is ever tried at each
+/// position. A branch is abandoned as soon as the parser rejects the tiles placed so far, since no
+/// later tile can make up for an earlier mismatch.
+fn _arrange(tiles: &[Tile], rules: &RuleSet, limit: Option<usize>, found: &mut Vec<Vec<Tile>>) {
+    let mut sorted = tiles.to_vec();
+    sorted.sort_by_key(_sort_key);
+    let mut used = vec![false; sorted.len()];
+    let mut prefix = Vec::new();
+    _arrange_from(&sorted, rules, limit, &mut used, &mut prefix, found);
+}
+
+fn _arrange_from(
+    tiles: &[Tile],
+    rules: &RuleSet,
+    limit: Option<usize>,
+    used: &mut [bool],
+    prefix: &mut Vec<Tile>,
+    found: &mut Vec<Vec<Tile>>,
+) {
+    if prefix.len() == tiles.len() {
+        if validate_set(prefix, rules).is_ok() {
+            found.push(prefix.clone());
+        }
+        return;
+    }
+    for i in 0..tiles.len() {
+        if used[i] || (i > 0 && !used[i - 1] && tiles[i - 1] == tiles[i]) {
+            continue;
+        }
+        prefix.push(tiles[i]);
+        if _is_viable_prefix(prefix, rules) {
+            used[i] = true;
+            _arrange_from(tiles, rules, limit, used, prefix, found);
+            used[i] = false;
+        }
+        prefix.pop();
+        if Some(found.len()) == limit {
+            return;
+        }
+    }
+}
+
+/// Order tiles by value and then color, with jokers last.
+fn _sort_key(tile: &Tile) -> (TileValue, u8) {
+    match tile {
+        Tile::Basic(t) => {
+            let color = match t.color {
+                TileColor::Black => 0,
+                TileColor::Red => 1,
+                TileColor::Blue => 2,
+                TileColor::Orange => 3,
+            };
+            (t.value, color)
+        }
+        Tile::Joker(j) => {
+            let variant = match j.variant {
+                JokerVariant::Single => 0,
+                JokerVariant::Double => 1,
+                JokerVariant::Mirror => 2,
+                JokerVariant::ColorChange => 3,
+            };
+            (14, variant)
+        }
+    }
+}
+
+/// Check that every tile in the set is a real tile, in play and available in the box.
+fn _check_tiles(set: &[Tile], rules: &RuleSet) -> Result<(), InvalidSet> {
+    for (index, tile) in set.iter().enumerate() {
+        match tile {
+            Tile::Basic(t) => {
+                if t.value == 0 || t.value > 13 {
+                    return _invalid(InvalidReason::InvalidTileValue, index);
+                }
+            }
+            Tile::Joker(j) => {
+                if !rules.allows(&j.variant) {
+                    return _invalid(InvalidReason::JokerNotAllowed, index);
+                }
+            }
+        }
+        let copies = set[..=index].iter().filter(|t| *t == tile).count();
+        if copies > rules.bag.copies(tile) as usize {
+            return _invalid(InvalidReason::TooManyCopies, index);
+        }
     }
+    Ok(())
+}
+
+/// Return the index of the first mirror joker in the set, if any.
+fn _mirror_axis(set: &[Tile]) -> Option<usize> {
+    set.iter().position(|tile| match tile {
+        Tile::Joker(j) => j.variant == JokerVariant::Mirror,
+        Tile::Basic(_) => false,
+    })
+}
+
+/// Run the parsing state machine over the positions of a set and return the state it ended in.
+fn _parse<I>(units: I, rules: &RuleSet) -> Result<Parsing, InvalidSet>
+where
+    I: IntoIterator<Item = (usize, Unit)>,
+{
+    let max_group_size = rules.max_group_size.min(4);
     let mut parsing = Parsing::Undetermined {
         tile_seen: None,
         size: 0,
     };
-    let mut tiles = set.iter().enumerate();
+    let mut previous = None;
+
+    for (position, (index, unit)) in units.into_iter().enumerate() {
+        // No run can be longer than 13 tiles, even when it wraps around.
+        if position >= 13 {
+            return _invalid(InvalidReason::ValueOutOfRange, index);
+        }
 
-    while let Some((index, tile)) = tiles.next() {
         match parsing {
             Parsing::Run {
                 ref mut last_value,
                 ref mut allow,
+                color: ref mut stretch,
                 ref mut size,
-            } => match tile {
-                Tile::Basic(t) => {
-                    _assert_valid_tile_value(t.value);
-                    if !allow[&t.color] {
-                        return false;
+            } => match unit {
+                Unit::Basic(color, value) => {
+                    if !allow.contains(color) {
+                        return match stretch {
+                            None => _invalid(InvalidReason::MissingColorSwitch, index),
+                            Some(_) => _invalid(InvalidReason::ColorMismatchInRun, index),
+                        };
                     }
-                    if t.value <= *size {
-                        return false;
-                    }
-                    *size += 1;
                     match last_value {
                         Some(ref mut val) => {
-                            *val += 1;
-                            if t.value != *val {
-                                return false;
+                            let expected = match _offset(*val, 1, rules) {
+                                Some(v) => v,
+                                None => return _invalid(InvalidReason::ValueOutOfRange, index),
+                            };
+                            if value < expected {
+                                return _invalid(InvalidReason::DescendingRun, index);
+                            }
+                            if value > expected {
+                                return _invalid(InvalidReason::GapInRun, index);
                             }
+                            *val = expected;
                         }
                         None => {
-                            *allow = Colors::only(t.color);
-                            *last_value = Some(t.value);
+                            // Check that the starting value of the run is valid.
+                            // Ex. C J 3 .. is valid
+                            //     C J 2 .. is NOT valid
+                            if _offset_back(value, *size, rules).is_none() {
+                                return _invalid(InvalidReason::ValueOutOfRange, index);
+                            }
+                            *last_value = Some(value);
                         }
                     }
+                    *size += 1;
+                    *allow = ColorSet::only(color);
+                    *stretch = Some(color);
                 }
-                Tile::Joker(j) => match j.variant {
-                    JokerVariant::Single => {
-                        *size += 1;
-                        match last_value {
-                            Some(ref mut val) => {
-                                *val += 1;
-                                if *val > 13 {
-                                    return false;
-                                }
-                            }
-                            None => continue,
-                        }
+                Unit::Joker => {
+                    *size += 1;
+                    if let Some(ref mut val) = last_value {
+                        *val = match _offset(*val, 1, rules) {
+                            Some(v) => v,
+                            None => return _invalid(InvalidReason::ValueOutOfRange, index),
+                        };
                     }
-                    JokerVariant::Double => {
-                        *size += 2;
-                        match last_value {
-                            Some(ref mut val) => {
-                                *val += 2;
-                                if *val > 13 {
-                                    return false;
-                                }
-                            }
-                            None => continue,
-                        }
+                }
+                Unit::ColorChange => {
+                    if previous == Some(Unit::ColorChange) && !rules.adjacent_color_changes {
+                        return _invalid(InvalidReason::AdjacentColorChanges, index);
                     }
-                    JokerVariant::Mirror => {
-                        return _is_symmetric(&set, index);
+                    *size += 1;
+                    if let Some(ref mut val) = last_value {
+                        *val = match _offset(*val, 1, rules) {
+                            Some(v) => v,
+                            None => return _invalid(InvalidReason::ValueOutOfRange, index),
+                        };
                     }
-                    JokerVariant::ColorChange => {
-                        *size += 1;
-                        match last_value {
-                            Some(ref mut val) => {
-                                *val += 1;
-                                if *val > 13 {
-                                    return false;
-                                }
 
-                                let allow_cnt = allow
-                                    .iter()
-                                    .filter(|(_, v)| **v)
-                                    .collect::<Vec<(&TileColor, &bool)>>()
-                                    .len();
-                                if allow_cnt == 1 {
-                                    for perm in allow.values_mut() {
-                                        if *perm {
-                                            *perm = false;
-                                        } else {
-                                            *perm = true;
-                                        }
-                                    }
-                                } else if allow_cnt == 3 {
-                                    *allow = Colors::all();
-                                } else {
-                                    panic!("Unexpected number of allowed colors ({}) upon color change", allow_cnt);
-                                }
-                            }
-                            None => {
-                                *allow = Colors::all();
-                            }
-                        }
-                    }
-                },
+                    // The next stretch must switch away from the color of the current stretch.
+                    // If the current stretch only contains jokers, its color can always be chosen
+                    // to differ from both of its neighbours, so every color is allowed.
+                    *allow = match stretch {
+                        Some(color) => ColorSet::except(*color),
+                        None => ColorSet::all(),
+                    };
+                    *stretch = None;
+                }
             },
             Parsing::Group {
-                ref mut value,
+                value: group_value,
                 ref mut allow,
                 ref mut size,
-            } => match tile {
-                Tile::Basic(t) => {
-                    _assert_valid_tile_value(t.value);
-                    if t.value != *value {
-                        return false;
+            } => match unit {
+                Unit::Basic(color, value) => {
+                    if value != group_value {
+                        return _invalid(InvalidReason::ValueMismatchInGroup, index);
                     }
-                    if !allow[&t.color] {
-                        return false;
+                    if !allow.contains(color) {
+                        return _invalid(InvalidReason::ColorRepeatedInGroup, index);
                     }
                     *size += 1;
-                    if *size > 4 {
-                        return false;
+                    if *size > max_group_size {
+                        return _invalid(InvalidReason::GroupTooLarge, index);
                     }
+                    allow.remove(color);
                 }
-                Tile::Joker(j) => match j.variant {
-                    JokerVariant::Single => {
-                        *size += 1;
-                        if *size > 4 {
-                            return false;
-                        }
-                    }
-                    JokerVariant::Double => {
-                        *size += 2;
-                        if *size > 4 {
-                            return false;
-                        }
-                    }
-                    JokerVariant::Mirror => {
-                        return _is_symmetric(&set, index);
-                    }
-                    JokerVariant::ColorChange => {
-                        return false;
+                Unit::Joker => {
+                    *size += 1;
+                    if *size > max_group_size {
+                        return _invalid(InvalidReason::GroupTooLarge, index);
                     }
-                },
+                }
+                Unit::ColorChange => {
+                    return _invalid(InvalidReason::ColorChangeInGroup, index);
+                }
             },
             Parsing::Undetermined {
                 ref mut tile_seen,
                 ref mut size,
-            } => match tile {
-                Tile::Basic(t) => {
-                    _assert_valid_tile_value(t.value);
-                    match tile_seen {
-                        Some((ts, dist)) => {
-                            if t.value == ts.value + *dist && t.color == ts.color {
-                                // Check that the starting value of the run is valid.
-                                // Ex. J J 3 4 .. is valid
-                                //     J J 2 3 .. is NOT valid
-                                if *size > t.value {
-                                    return false;
-                                }
+            } => match unit {
+                Unit::Basic(color, value) => match tile_seen {
+                    Some((ts, dist)) => {
+                        if Some(value) == _offset(ts.value, *dist, rules) && color == ts.color {
+                            // Check that the starting value of the run is valid.
+                            // Ex. J J 3 4 .. is valid
+                            //     J J 2 3 .. is NOT valid
+                            if _offset_back(value, *size, rules).is_none() {
+                                return _invalid(InvalidReason::ValueOutOfRange, index);
+                            }
 
-                                let allow = Colors::only(t.color);
-                                parsing = Parsing::Run {
-                                    last_value: Some(t.value),
-                                    allow: allow,
-                                    size: *size + 1,
-                                };
-                            } else if t.value == ts.value && t.color != ts.color {
-                                // Check that the length of the group is valid.
-                                // Ex. J J Red Blue   .. is valid
-                                //     J DJ Red Blue .. is NOT valid
-                                if *size > 3 {
-                                    return false;
-                                }
+                            parsing = Parsing::Run {
+                                last_value: Some(value),
+                                allow: ColorSet::only(color),
+                                color: Some(color),
+                                size: *size + 1,
+                            };
+                        } else if value == ts.value && color != ts.color {
+                            // Check that the length of the group is valid.
+                            // Ex. J J Red Blue   .. is valid
+                            //     J DJ Red Blue .. is NOT valid
+                            if *size >= max_group_size {
+                                return _invalid(InvalidReason::GroupTooLarge, index);
+                            }
 
-                                let mut allow = Colors::all();
-                                allow.insert(t.color, false);
-                                allow.insert(ts.color, false);
+                            let mut allow = ColorSet::all();
+                            allow.remove(color);
+                            allow.remove(ts.color);
 
-                                parsing = Parsing::Group {
-                                    value: ts.value,
-                                    allow: allow,
-                                    size: *size + 1,
-                                };
-                            } else {
-                                return false;
-                            }
-                        }
-                        None => {
-                            *size += 1;
-                            *tile_seen = Some((BasicTile::new(t.color, t.value), 1));
-
-                            if *size > 4 {
-                                // Check that the current sequence is not an invalid run.
-                                // (Group can be ruled out due to the total length being >= 5.)
-                                // Ex. J J DJ 5 .. is valid
-                                //     J J DJ 4 .. is NOT valid
-                                if t.value <= *size {
-                                    return false;
+                            parsing = Parsing::Group {
+                                value,
+                                allow,
+                                size: *size + 1,
+                            };
+                        } else if color == ts.color {
+                            // The tile can only belong to a run with the first tile.
+                            return match value {
+                                v if v == ts.value => {
+                                    _invalid(InvalidReason::ColorRepeatedInGroup, index)
                                 }
-
-                                let allow = Colors::only(t.color);
-                                parsing = Parsing::Run {
-                                    last_value: None,
-                                    allow: allow,
-                                    size: *size,
+                                v if v < ts.value + *dist => {
+                                    _invalid(InvalidReason::DescendingRun, index)
                                 }
-                            }
+                                _ => _invalid(InvalidReason::GapInRun, index),
+                            };
+                        } else if Some(value) == _offset(ts.value, *dist, rules) {
+                            return _invalid(InvalidReason::ColorMismatchInRun, index);
+                        } else {
+                            return _invalid(InvalidReason::ValueMismatchInGroup, index);
                         }
                     }
-                }
-                Tile::Joker(j) => match j.variant {
-                    JokerVariant::Single => {
-                        if let Some((_, dist)) = tile_seen {
-                            *dist += 1;
-                        }
+                    None => {
                         *size += 1;
-                        if *size > 4 {
-                            match tile_seen {
-                                Some((ts, dist)) => {
-                                    let allow = Colors::only(ts.color);
-                                    parsing = Parsing::Run {
-                                        last_value: Some(ts.value + *dist),
-                                        allow: allow,
-                                        size: *size,
-                                    }
-                                }
-                                None => {
-                                    let allow = Colors::all();
-                                    parsing = Parsing::Run {
-                                        last_value: None,
-                                        allow: allow,
-                                        size: *size,
-                                    }
-                                }
+                        *tile_seen = Some((BasicTile { color, value }, 1));
+
+                        if *size > max_group_size {
+                            // Check that the current sequence is not an invalid run.
+                            // (Group can be ruled out due to the total length exceeding the maximum group size.)
+                            // Ex. J J DJ 5 .. is valid
+                            //     J J DJ 4 .. is NOT valid
+                            if _offset_back(value, *size - 1, rules).is_none() {
+                                return _invalid(InvalidReason::ValueOutOfRange, index);
+                            }
+
+                            parsing = Parsing::Run {
+                                last_value: Some(value),
+                                allow: ColorSet::only(color),
+                                color: Some(color),
+                                size: *size,
                             }
                         }
                     }
-                    JokerVariant::Double => {
-                        if let Some((_, dist)) = tile_seen {
-                            *dist += 2;
-                        }
-                        *size += 2;
-                        if *size > 4 {
-                            match tile_seen {
-                                Some((ts, dist)) => {
-                                    let allow = Colors::only(ts.color);
-                                    parsing = Parsing::Run {
-                                        last_value: Some(ts.value + *dist - 1),
-                                        allow: allow,
-                                        size: *size,
-                                    }
-                                }
-                                None => {
-                                    let allow = Colors::all();
-                                    parsing = Parsing::Run {
-                                        last_value: None,
-                                        allow: allow,
-                                        size: *size,
-                                    }
+                },
+                Unit::Joker => {
+                    if let Some((_, dist)) = tile_seen {
+                        *dist += 1;
+                    }
+                    *size += 1;
+                    if *size > max_group_size {
+                        // Group can be ruled out due to the total length exceeding the maximum group size.
+                        parsing = match tile_seen {
+                            Some((ts, dist)) => {
+                                // Check that the run neither starts below 1 nor ends above 13.
+                                // Ex. J 2 J J J .. is valid
+                                //     2 J J J J .. is valid
+                                //     J J 2 J J .. is NOT valid
+                                let start = _offset_back(ts.value, *size - *dist, rules);
+                                let val = match _offset(ts.value, *dist - 1, rules) {
+                                    Some(v) if start.is_some() => v,
+                                    _ => return _invalid(InvalidReason::ValueOutOfRange, index),
+                                };
+                                Parsing::Run {
+                                    last_value: Some(val),
+                                    allow: ColorSet::only(ts.color),
+                                    color: Some(ts.color),
+                                    size: *size,
                                 }
                             }
+                            None => Parsing::Run {
+                                last_value: None,
+                                allow: ColorSet::all(),
+                                color: None,
+                                size: *size,
+                            },
                         }
                     }
-                    JokerVariant::Mirror => {
-                        return _is_symmetric(&set, index);
-                    }
-                    JokerVariant::ColorChange => match tile_seen {
-                        Some((ts, _)) => {
-                            let allow = Colors::except(ts.color);
-                            parsing = Parsing::Run {
-                                last_value: Some(ts.value + 1),
-                                allow: allow,
-                                size: *size + 1,
+                }
+                Unit::ColorChange => {
+                    parsing = match tile_seen {
+                        Some((ts, dist)) => {
+                            // Check that the run neither starts below 1 nor ends above 13.
+                            let start = _offset_back(ts.value, *size - *dist, rules);
+                            let val = match _offset(ts.value, *dist, rules) {
+                                Some(v) if start.is_some() => v,
+                                _ => return _invalid(InvalidReason::ValueOutOfRange, index),
                             };
-                        }
-                        None => {
-                            let allow = Colors::all();
-                            parsing = Parsing::Run {
-                                last_value: None,
-                                allow: allow,
+                            Parsing::Run {
+                                last_value: Some(val),
+                                allow: ColorSet::except(ts.color),
+                                color: None,
                                 size: *size + 1,
                             }
                         }
-                    },
-                },
+                        None => Parsing::Run {
+                            last_value: None,
+                            allow: ColorSet::all(),
+                            color: None,
+                            size: *size + 1,
+                        },
+                    }
+                }
             },
         }
+        previous = Some(unit);
     }
-    return true;
+    Ok(parsing)
 }
 
-/// Assert that a given tile value is between 1 and 13 (inclusive).
-fn _assert_valid_tile_value(value: TileValue) {
-    if value == 0 || value > 13 {
-        panic!(
-            "Illegal tile value {}: tiles should be 0 < value <= 13",
-            value
-        );
+/// Return the kind of set formed by the positions of a set, given the state the parser ended in
+/// after reading them. The colors of the set are read from the positions again, so that the
+/// parser itself never has to collect them.
+fn _kind<I>(parsing: Parsing, units: I, rules: &RuleSet) -> SetKind
+where
+    I: Iterator<Item = (usize, Unit)>,
+{
+    match parsing {
+        Parsing::Run {
+            last_value, size, ..
+        } => {
+            // A new stretch begins after every color-change joker, and takes the color of its
+            // basic tiles.
+            let mut colors = vec![None];
+            for (_, unit) in units {
+                match unit {
+                    Unit::Basic(color, _) => *colors.last_mut().unwrap() = Some(color),
+                    Unit::ColorChange => colors.push(None),
+                    Unit::Joker => {}
+                }
+            }
+            SetKind::Run {
+                colors,
+                start: last_value.and_then(|val| _offset_back(val, size - 1, rules)),
+                end: last_value,
+            }
+        }
+        Parsing::Group { value, .. } => SetKind::Group {
+            value,
+            colors: units
+                .filter_map(|(_, unit)| match unit {
+                    Unit::Basic(color, _) => Some(color),
+                    _ => None,
+                })
+                .collect(),
+        },
+        Parsing::Undetermined { tile_seen, size } => match tile_seen {
+            Some((ts, dist)) => {
+                // With a single basic tile, the sequence is always a valid group, but it is only
+                // undetermined if it is also a valid run.
+                // Ex. J 5 J   .. is undetermined
+                //     J J J 1 .. is a group
+                let start = _offset_back(ts.value, size - dist, rules);
+                let end = _offset(ts.value, dist - 1, rules);
+                if start.is_some() && end.is_some() {
+                    SetKind::Undetermined
+                } else {
+                    SetKind::Group {
+                        value: ts.value,
+                        colors: vec![ts.color],
+                    }
+                }
+            }
+            None => SetKind::Undetermined,
+        },
+    }
+}
+
+/// Return the value `by` positions after the given value in a run, or None if the run would go
+/// above 13 without being allowed to wrap around.
+fn _offset(value: TileValue, by: u8, rules: &RuleSet) -> Option<TileValue> {
+    if value + by <= 13 {
+        Some(value + by)
+    } else if rules.wrap_around {
+        Some((value - 1 + by) % 13 + 1)
+    } else {
+        None
+    }
+}
+
+/// Return the value `by` positions before the given value in a run, or None if the run would go
+/// below 1 without being allowed to wrap around.
+fn _offset_back(value: TileValue, by: u8, rules: &RuleSet) -> Option<TileValue> {
+    if value > by {
+        Some(value - by)
+    } else if rules.wrap_around {
+        Some((value + 12 - by % 13) % 13 + 1)
+    } else {
+        None
+    }
+}
+
+/// Reject a set for the given reason at the given tile index.
+fn _invalid<T>(reason: InvalidReason, index: usize) -> Result<T, InvalidSet> {
+    Err(InvalidSet { reason, index })
+}
+
+/// Expand a sequence of tiles into the positions they occupy, each paired with the index of the
+/// tile it belongs to. Mirror jokers are rejected, since a set can only be folded once. The values
+/// of basic tiles must already have been checked by `_check_tiles`.
+fn _expand<'a, I>(tiles: I) -> Result<Vec<(usize, Unit)>, InvalidSet>
+where
+    I: Iterator<Item = (usize, &'a Tile)>,
+{
+    let mut units = Vec::new();
+    for (index, tile) in tiles {
+        match _unit(tile) {
+            Some((unit, count)) => units.extend(iter::repeat_n((index, unit), count)),
+            None => return _invalid(InvalidReason::MisplacedMirror, index),
+        }
+    }
+    Ok(units)
+}
+
+/// Same as `_expand`, but without collecting the positions. Mirror jokers occupy no position, so
+/// they must already have been ruled out.
+fn _positions<'a, I>(tiles: I) -> impl Iterator<Item = (usize, Unit)> + 'a
+where
+    I: Iterator<Item = (usize, &'a Tile)> + 'a,
+{
+    tiles.flat_map(|(index, tile)| {
+        let (unit, count) = _unit(tile).unwrap_or((Unit::Joker, 0));
+        iter::repeat_n((index, unit), count)
+    })
+}
+
+/// Return the position a tile occupies in a set, and the number of times it occupies it, or None
+/// for a mirror joker.
+fn _unit(tile: &Tile) -> Option<(Unit, usize)> {
+    match tile {
+        Tile::Basic(t) => Some((Unit::Basic(t.color, t.value), 1)),
+        Tile::Joker(j) => match j.variant {
+            JokerVariant::Single => Some((Unit::Joker, 1)),
+            JokerVariant::Double => Some((Unit::Joker, 2)),
+            JokerVariant::Mirror => None,
+            JokerVariant::ColorChange => Some((Unit::ColorChange, 1)),
+        },
     }
 }
 
-/// Return whether the set contains logically symmetric tiles across the given axis.
+/// Fold the set across the mirror joker at the given axis, and return the positions of one half of
+/// the set with the tiles on both sides combined. Return an error if the two sides are not
+/// logically symmetric.
 ///
 /// Examples:
-/// 3 4 5 | 5 4 3 .. is symmetric
-/// 3 J 5 | 5 4 3 .. is symmetric
-/// 3 J 5 | 5 DJ  .. is symmetric
+/// 3 4 5 | 5 4 3 .. folds into 3 4 5
+/// 3 J 5 | 5 4 3 .. folds into 3 4 5
+/// 3 J 5 | 5 DJ  .. folds into 3 J 5
 /// DJ DJ | 5 4 3 .. is NOT symmetric
 ///
 ///
 /// Implementation:
-/// Two pointers traverse outward in opposite directions from the axis, and we compare the two
-/// tiles that they point to. If in any given iteration, the two tiles are not logically
-/// equivalent, we know that the set is not symmetric. We stop traversing once either pointer
-/// reaches the logical end of the set. We do a final check to see if both pointers have reached
-/// opposite ends of the set.
-fn _is_symmetric(set: &Vec<Tile>, axis: usize) -> bool {
+/// Both sides are expanded into positions, with the right side read from right to left. The two
+/// sides must occupy the same number of positions. A basic tile on one side must face either the
+/// same basic tile or a joker on the other side, and color-change jokers must face each other.
+/// The folded half is then validated by the parser like any other sequence.
+fn _reflect(set: &[Tile], axis: usize) -> Result<Vec<(usize, Unit)>, InvalidSet> {
+    _check_mirror(set, axis)?;
+    Ok(_folded(set, axis).collect())
+}
+
+/// Check that the set can be folded across the mirror joker at the given axis, as `_reflect` does,
+/// without collecting the positions of either side.
+fn _check_mirror(set: &[Tile], axis: usize) -> Result<(), InvalidSet> {
     if axis == 0 || axis == set.len() - 1 {
-        return false;
+        return _invalid(InvalidReason::MisplacedMirror, axis);
+    }
+    // The right side is read from right to left, so the last mirror joker is the one rejected.
+    let mirror = set.iter().rposition(|tile| _unit(tile).is_none());
+    if let Some(index) = mirror.filter(|&index| index > axis) {
+        return _invalid(InvalidReason::MisplacedMirror, index);
     }
+    let left = || _positions(set.iter().enumerate().take(axis));
+    let right = || _positions(set.iter().enumerate().skip(axis + 1).rev());
 
-    // Left and right pointers.
-    let mut left = axis - 1;
-    let mut right = axis + 1;
+    for (l, r) in left().zip(right()) {
+        if _fold(l, r).is_none() {
+            return _invalid(InvalidReason::AsymmetricMirror, r.0);
+        }
+    }
+    let (sides, other) = (left().count(), right().count());
+    if let Some((index, _)) = left().nth(other) {
+        return _invalid(InvalidReason::AsymmetricMirror, index);
+    }
+    if let Some((index, _)) = right().nth(sides) {
+        return _invalid(InvalidReason::AsymmetricMirror, index);
+    }
+    Ok(())
+}
 
-    // Record-keeping flags for incrementing pointers.
-    let mut incr_left: bool;
-    let mut incr_right: bool;
+/// Return the positions of one half of a set folded across the mirror joker at the given axis,
+/// once `_check_mirror` has accepted it.
+fn _folded(set: &[Tile], axis: usize) -> impl Iterator<Item = (usize, Unit)> + '_ {
+    let left = _positions(set.iter().enumerate().take(axis));
+    let right = _positions(set.iter().enumerate().skip(axis + 1).rev());
+    left.zip(right).filter_map(|(l, r)| _fold(l, r))
+}
 
-    // Record-keeping flags for incrementing pointers to double jokers.
-    let mut incr_dj_left = false;
-    let mut incr_dj_right = false;
+/// Combine two positions facing each other across a mirror joker, keeping the index of the tile
+/// that determines the combined position. Return None if they cannot stand for the same tile.
+fn _fold(left: (usize, Unit), right: (usize, Unit)) -> Option<(usize, Unit)> {
+    match (left.1, right.1) {
+        (Unit::Basic(..), Unit::Basic(..)) if left.1 == right.1 => Some(left),
+        (Unit::Basic(..), Unit::Joker) => Some(left),
+        (Unit::Joker, Unit::Basic(..)) => Some(right),
+        (Unit::Joker, Unit::Joker) => Some(left),
+        (Unit::ColorChange, Unit::ColorChange) => Some(left),
+        _ => None,
+    }
+}
 
-    loop {
-        match &set[left] {
-            Tile::Basic(bl) => match &set[right] {
-                Tile::Basic(br) => {
-                    if bl != br {
-                        return false;
-                    }
-                    incr_left = true;
-                    incr_right = true;
-                }
-                Tile::Joker(jr) => match jr.variant {
-                    JokerVariant::Single => {
-                        incr_left = true;
-                        incr_right = true;
-                    }
-                    JokerVariant::Double => {
-                        if incr_dj_right {
-                            incr_right = true;
-                            incr_dj_right = false;
-                        } else {
-                            incr_right = false;
-                            incr_dj_right = true;
-                        }
-                        incr_left = true;
-                    }
-                    JokerVariant::Mirror => {
-                        return false;
-                    }
-                    JokerVariant::ColorChange => {
-                        return false;
-                    }
-                },
-            },
-            Tile::Joker(jl) => match jl.variant {
-                JokerVariant::Single => match &set[right] {
-                    Tile::Basic(_) => {
-                        incr_left = true;
-                        incr_right = true;
-                    }
-                    Tile::Joker(jr) => match jr.variant {
-                        JokerVariant::Single => {
-                            incr_left = true;
-                            incr_right = true;
-                        }
-                        JokerVariant::Double => {
-                            if incr_dj_right {
-                                incr_right = true;
-                                incr_dj_right = false;
-                            } else {
-                                incr_right = false;
-                                incr_dj_right = true;
-                            }
-                            incr_left = true;
-                        }
-                        JokerVariant::Mirror => {
-                            return false;
-                        }
-                        JokerVariant::ColorChange => {
-                            return false;
-                        }
-                    },
-                },
-                JokerVariant::Double => match &set[right] {
-                    Tile::Basic(_) => {
-                        if incr_dj_left {
-                            incr_left = true;
-                            incr_dj_left = false;
-                        } else {
-                            incr_left = false;
-                            incr_dj_left = true;
-                        }
-                        incr_right = true;
-                    }
-                    Tile::Joker(jr) => match jr.variant {
-                        JokerVariant::Single => {
-                            if incr_dj_left {
-                                incr_left = true;
-                                incr_dj_left = false;
-                            } else {
-                                incr_left = false;
-                                incr_dj_left = true;
-                            }
-                            incr_right = true;
-                        }
-                        JokerVariant::Double => {
-                            if incr_dj_left {
-                                incr_left = true;
-                                incr_dj_left = false;
-                            } else {
-                                incr_left = false;
-                                incr_dj_left = true;
-                            }
-                            if incr_dj_right {
-                                incr_right = true;
-                                incr_dj_right = false;
-                            } else {
-                                incr_right = false;
-                                incr_dj_right = true;
-                            }
-                        }
-                        JokerVariant::Mirror => {
-                            return false;
-                        }
-                        JokerVariant::ColorChange => {
-                            return false;
-                        }
-                    },
-                },
-                JokerVariant::Mirror => {
-                    return false;
-                }
-                JokerVariant::ColorChange => match &set[right] {
-                    Tile::Basic(_) => {
-                        return false;
-                    }
-                    Tile::Joker(jr) => match jr.variant {
-                        JokerVariant::Single => {
-                            return false;
-                        }
-                        JokerVariant::Double => {
-                            return false;
-                        }
-                        JokerVariant::Mirror => {
-                            return false;
-                        }
-                        JokerVariant::ColorChange => {
-                            incr_left = true;
-                            incr_right = true;
-                        }
-                    },
-                },
-            },
-        }
-        if left == 0 {
-            if let Tile::Joker(joker) = &set[left] {
-                if joker.variant == JokerVariant::Double && incr_dj_left {
-                    if incr_right {
-                        right += 1;
-                    }
-                    continue;
-                }
-            }
-        }
-        if right == set.len() - 1 {
-            if let Tile::Joker(joker) = &set[right] {
-                if joker.variant == JokerVariant::Double && incr_dj_right {
-                    if incr_left {
-                        left -= 1;
-                    }
-                    continue;
+/// Return whether the given tiles could begin a valid set, i.e. whether the parser accepts every
+/// tile seen so far. Tiles past a mirror joker are only checked against the tiles they face.
+fn _is_viable_prefix(prefix: &[Tile], rules: &RuleSet) -> bool {
+    if _check_tiles(prefix, rules).is_err() {
+        return false;
+    }
+    match _mirror_axis(prefix) {
+        Some(axis) => {
+            if axis == 0 {
+                return false;
+            }
+            let mut units = match _expand(prefix.iter().enumerate().take(axis)) {
+                Ok(units) => units,
+                Err(_) => return false,
+            };
+            let right = match _expand(prefix.iter().enumerate().skip(axis + 1)) {
+                Ok(right) => right,
+                Err(_) => return false,
+            };
+            if right.len() > units.len() {
+                return false;
+            }
+            // The tiles to the right of the mirror face the tiles to the left from the mirror
+            // outward.
+            let len = units.len();
+            for (k, &r) in right.iter().enumerate() {
+                match _fold(units[len - 1 - k], r) {
+                    Some(unit) => units[len - 1 - k] = unit,
+                    None => return false,
                 }
             }
+            _parse(units, rules).is_ok()
         }
-        if left == 0 || right == set.len() - 1 {
-            break;
-        }
-        if incr_left {
-            left -= 1;
-        }
-        if incr_right {
-            right += 1;
-        }
+        None => match _expand(prefix.iter().enumerate()) {
+            Ok(units) => _parse(units, rules).is_ok(),
+            Err(_) => false,
+        },
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+    use crate::tiles::{deserialize_declared_set, Joker, TileBag};
+
+    // BASIC RUNS
+
+    #[test]
+    fn test_valid_run() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_descending_run() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_short_run() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    // BASIC GROUPS
+
+    #[test]
+    fn test_valid_group_with_length_4() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_group_with_length_3() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_group_with_repeated_color() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_group_run_hybrid() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 10).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    // SINGLE JOKER
+
+    #[test]
+    fn test_valid_run_with_single_joker() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 10).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_run_with_single_joker() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_single_joker_2() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_single_joker_3() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 13).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_valid_group_with_single_joker() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_group_with_single_joker_2() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+        ];
+        let mut rules = RuleSet::special_jokers();
+        rules.bag.single_jokers = 3;
+        assert!(validate_set(&set, &rules).is_ok());
+    }
+
+    #[test]
+    fn test_valid_run_with_two_single_jokers() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_run_with_more_single_jokers_than_in_the_box() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    // DOUBLE JOKER
+
+    #[test]
+    fn test_valid_run_with_double_joker() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_run_with_double_joker() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_valid_run_with_two_double_jokers() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 3).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_run_with_double_joker_2() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 11).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_run_with_double_joker_2() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_group_with_double_joker() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    // MIRROR JOKER
+
+    #[test]
+    fn test_valid_group_with_mirror() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_run_with_mirror() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_group_with_mirror() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_mirror() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_mirror_2() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_two_mirrors() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    // COLOR CHANGE JOKER
+
+    #[test]
+    fn test_valid_run_with_color_change() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 10).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_run_with_color_change() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 10).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_color_change_without_number_skip() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_valid_run_with_two_color_changes() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Orange, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 10).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_run_with_two_color_change_sandwich() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_run_with_two_color_change_adjacent() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Orange, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_adjacent_color_change_house_rule() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Orange, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_adjacent_color_change_house_rule_2() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Orange, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 9).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    // MIXED JOKERS
+
+    #[test]
+    fn test_valid_mixed_1() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_mixed_2() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_mixed_3() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_mixed_4() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_mixed_5() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_valid_mixed_6() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_mixed_7() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_valid_mixed_8() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    // JOKERS AT THE EDGES OF A SET
+
+    #[test]
+    fn test_valid_run_with_leading_jokers() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_valid_run_with_jokers_around_basic_tile() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_run_starting_at_zero() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_valid_run_with_double_jokers_before_basic_tile() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 5).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    #[test]
+    fn test_invalid_run_with_joker_before_one() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_double_jokers_at_both_ends() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 3).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_double_joker_before_two() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_double_joker_after_eleven() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 11).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    // MIRROR AND COLOR-CHANGE JOKERS
+
+    #[test]
+    fn test_invalid_run_with_mismatched_joker_across_mirror() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_joker_facing_lower_tile_across_mirror() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Orange, 13).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 12).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_color_change_and_two_colors_after() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 10).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_invalid_run_with_color_change_and_mixed_colors() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 10).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 12).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 13).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), false);
+    }
+
+    #[test]
+    fn test_valid_run_with_joker_before_color_change() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 1).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 4).unwrap()),
+        ];
+        assert_eq!(is_valid_set(&set), true);
+    }
+
+    // CLASSIFICATION
+
+    #[test]
+    fn test_classify_run() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+        ];
+        let expected = SetKind::Run {
+            colors: vec![Some(TileColor::Red)],
+            start: Some(5),
+            end: Some(9),
+        };
+        assert_eq!(
+            classify_set(&set, &RuleSet::special_jokers()),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn test_classify_run_with_color_changes() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 10).unwrap()),
+        ];
+        let expected = SetKind::Run {
+            colors: vec![None, Some(TileColor::Orange), None, Some(TileColor::Blue)],
+            start: Some(5),
+            end: Some(10),
+        };
+        let mut rules = RuleSet::special_jokers();
+        rules.bag.color_change_jokers = 3;
+        assert_eq!(classify_set(&set, &rules), Some(expected));
+    }
+
+    #[test]
+    fn test_classify_run_without_basic_tiles() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        let expected = SetKind::Run {
+            colors: vec![None, None],
+            start: None,
+            end: None,
+        };
+        assert_eq!(
+            classify_set(&set, &RuleSet::special_jokers()),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn test_classify_group() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        let expected = SetKind::Group {
+            value: 7,
+            colors: vec![TileColor::Red, TileColor::Black, TileColor::Orange],
+        };
+        assert_eq!(
+            classify_set(&set, &RuleSet::special_jokers()),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn test_classify_group_with_single_basic_tile() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 2).unwrap()),
+        ];
+        let expected = SetKind::Group {
+            value: 2,
+            colors: vec![TileColor::Blue],
+        };
+        assert_eq!(
+            classify_set(&set, &RuleSet::special_jokers()),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn test_classify_undetermined() {
+        let set = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        assert_eq!(
+            classify_set(&set, &RuleSet::special_jokers()),
+            Some(SetKind::Undetermined)
+        );
+    }
+
+    #[test]
+    fn test_classify_mirror() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+        ];
+        let expected = SetKind::Mirror(Box::new(SetKind::Run {
+            colors: vec![Some(TileColor::Blue), Some(TileColor::Red)],
+            start: Some(6),
+            end: Some(8),
+        }));
+        assert_eq!(
+            classify_set(&set, &RuleSet::special_jokers()),
+            Some(expected)
+        );
     }
 
-    if left != 0 || right != set.len() - 1 {
-        return false;
+    #[test]
+    fn test_classify_invalid() {
+        let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+        ];
+        assert_eq!(classify_set(&set, &RuleSet::special_jokers()), None);
     }
-    return true;
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    // BASIC RUNS
+    // INVALID REASONS
 
     #[test]
-    fn test_valid_run() {
+    fn test_reason_too_short() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 5)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = InvalidSet {
+            reason: InvalidReason::TooShort,
+            index: 2,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
     #[test]
-    fn test_invalid_descending_run() {
+    fn test_reason_invalid_tile_value() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Blue, 9)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 12).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 13).unwrap()),
+            Tile::Basic(BasicTile {
+                color: TileColor::Blue,
+                value: 14,
+            }),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = InvalidSet {
+            reason: InvalidReason::InvalidTileValue,
+            index: 2,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
     #[test]
-    fn test_invalid_short_run() {
+    fn test_reason_descending_run() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Blue, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = InvalidSet {
+            reason: InvalidReason::DescendingRun,
+            index: 1,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
-    // BASIC GROUPS
-
     #[test]
-    fn test_valid_group_with_length_4() {
+    fn test_reason_value_out_of_range() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Orange, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 13).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = InvalidSet {
+            reason: InvalidReason::ValueOutOfRange,
+            index: 3,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
     #[test]
-    fn test_valid_group_with_length_3() {
+    fn test_reason_color_repeated_in_group() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Orange, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = InvalidSet {
+            reason: InvalidReason::ColorRepeatedInGroup,
+            index: 2,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
     #[test]
-    fn test_invalid_group_with_repeated_color() {
+    fn test_reason_group_too_large() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Orange, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = InvalidSet {
+            reason: InvalidReason::GroupTooLarge,
+            index: 3,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
     #[test]
-    fn test_invalid_group_run_hybrid() {
+    fn test_reason_missing_color_switch() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Orange, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 9)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 10)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 10).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = InvalidSet {
+            reason: InvalidReason::MissingColorSwitch,
+            index: 3,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
-    // SINGLE JOKER
-
     #[test]
-    fn test_valid_run_with_single_joker() {
+    fn test_reason_asymmetric_mirror() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 10)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = InvalidSet {
+            reason: InvalidReason::AsymmetricMirror,
+            index: 8,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
     #[test]
-    fn test_invalid_run_with_single_joker() {
+    fn test_reason_misplaced_mirror() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 9)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = InvalidSet {
+            reason: InvalidReason::MisplacedMirror,
+            index: 3,
+        };
+        assert_eq!(
+            validate_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
+    // JOKER RESOLUTION
+
     #[test]
-    fn test_invalid_run_with_single_joker_2() {
+    fn test_resolve_jokers_in_run() {
         let set = vec![
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 1)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 2)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 3)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = vec![
+            (
+                0,
+                JokerRole::Single(StandIn {
+                    color: Some(TileColor::Red),
+                    value: Some(5),
+                }),
+            ),
+            (
+                2,
+                JokerRole::Double(
+                    StandIn {
+                        color: Some(TileColor::Red),
+                        value: Some(7),
+                    },
+                    StandIn {
+                        color: Some(TileColor::Red),
+                        value: Some(8),
+                    },
+                ),
+            ),
+        ];
+        assert_eq!(
+            resolve_jokers(&set, &RuleSet::special_jokers()),
+            Ok(expected)
+        );
     }
 
     #[test]
-    fn test_invalid_run_with_single_joker_3() {
+    fn test_resolve_jokers_in_group() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 11)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 12)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 13)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = vec![(
+            1,
+            JokerRole::Single(StandIn {
+                color: Some(TileColor::Blue),
+                value: Some(7),
+            }),
+        )];
+        assert_eq!(
+            resolve_jokers(&set, &RuleSet::special_jokers()),
+            Ok(expected)
+        );
     }
 
     #[test]
-    fn test_valid_group_with_single_joker() {
+    fn test_resolve_jokers_in_incomplete_group() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = vec![(
+            1,
+            JokerRole::Single(StandIn {
+                color: None,
+                value: Some(7),
+            }),
+        )];
+        assert_eq!(
+            resolve_jokers(&set, &RuleSet::special_jokers()),
+            Ok(expected)
+        );
     }
 
     #[test]
-    fn test_valid_group_with_single_joker_2() {
+    fn test_resolve_jokers_in_undetermined_set() {
         let set = vec![
             Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 1)),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let unknown = StandIn {
+            color: None,
+            value: None,
+        };
+        let expected = vec![
+            (0, JokerRole::Single(unknown)),
+            (2, JokerRole::Single(unknown)),
+        ];
+        assert_eq!(
+            resolve_jokers(&set, &RuleSet::special_jokers()),
+            Ok(expected)
+        );
     }
 
     #[test]
-    fn test_valid_run_with_two_single_jokers() {
+    fn test_resolve_jokers_across_mirror() {
         let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
             Tile::Joker(Joker::new(JokerVariant::Single)),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let blue_6 = StandIn {
+            color: Some(TileColor::Blue),
+            value: Some(6),
+        };
+        let red_8 = StandIn {
+            color: Some(TileColor::Red),
+            value: Some(8),
+        };
+        let expected = vec![
+            (
+                1,
+                JokerRole::ColorChange {
+                    value: Some(7),
+                    from: Some(TileColor::Blue),
+                    to: Some(TileColor::Red),
+                },
+            ),
+            (2, JokerRole::Single(red_8)),
+            (
+                3,
+                JokerRole::Mirror(vec![
+                    red_8,
+                    StandIn {
+                        color: None,
+                        value: Some(7),
+                    },
+                    blue_6,
+                ]),
+            ),
+            (
+                5,
+                JokerRole::ColorChange {
+                    value: Some(7),
+                    from: Some(TileColor::Red),
+                    to: Some(TileColor::Blue),
+                },
+            ),
+            (6, JokerRole::Single(blue_6)),
+        ];
+        assert_eq!(
+            resolve_jokers(&set, &RuleSet::special_jokers()),
+            Ok(expected)
+        );
     }
 
     #[test]
-    #[ignore]
-    fn test_invalid_run_with_more_single_jokers_than_in_the_box() {
+    fn test_resolve_jokers_in_invalid_set() {
         let set = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = InvalidSet {
+            reason: InvalidReason::DescendingRun,
+            index: 2,
+        };
+        assert_eq!(
+            resolve_jokers(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
-    // DOUBLE JOKER
+    // JOKER DECLARATIONS
 
     #[test]
-    fn test_valid_run_with_double_joker() {
-        let set = vec![
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 9)),
-        ];
-        assert_eq!(is_valid_set(&set), true);
+    fn test_declared_jokers_fit() {
+        let rules = RuleSet::special_jokers();
+        let set = deserialize_declared_set("r5 j=r6 r7").unwrap();
+        assert!(validate_declared_set(&set, &rules).is_ok());
+        let set = deserialize_declared_set("u3 d=u4u5 u6 j=u7").unwrap();
+        assert!(validate_declared_set(&set, &rules).is_ok());
+        let set = deserialize_declared_set("a8 j=o8 r8 j").unwrap();
+        assert!(validate_declared_set(&set, &rules).is_ok());
     }
 
     #[test]
-    fn test_invalid_run_with_double_joker() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Blue, 7)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-        ];
-        assert_eq!(is_valid_set(&set), false);
+    fn test_declared_joker_mismatch() {
+        let rules = RuleSet::special_jokers();
+        let expected = InvalidSet {
+            reason: InvalidReason::DeclarationMismatch,
+            index: 1,
+        };
+        let set = deserialize_declared_set("r5 j=r8 r7").unwrap();
+        assert_eq!(validate_declared_set(&set, &rules), Err(expected.clone()));
+        let set = deserialize_declared_set("r5 d=r7r6 r8").unwrap();
+        assert_eq!(validate_declared_set(&set, &rules), Err(expected));
     }
 
     #[test]
-    fn test_valid_run_with_two_double_jokers() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Blue, 3)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-        ];
-        assert_eq!(is_valid_set(&set), true);
+    fn test_declared_jokers_checked_together() {
+        let set = deserialize_declared_set("r7 j=u7 j=u7").unwrap();
+        let expected = InvalidSet {
+            reason: InvalidReason::DeclarationMismatch,
+            index: 2,
+        };
+        assert_eq!(
+            validate_declared_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
     }
 
     #[test]
-    fn test_valid_run_with_double_joker_2() {
+    fn test_declaration_on_wrong_tile() {
+        let rules = RuleSet::special_jokers();
+        let red = |value| BasicTile::new(TileColor::Red, value).unwrap();
+        let mut set = deserialize_declared_set("r5 j r7").unwrap();
+        set.declarations = vec![(0, Declaration::Single(red(5)))];
+        let expected = InvalidSet {
+            reason: InvalidReason::DeclarationMismatch,
+            index: 0,
+        };
+        assert_eq!(validate_declared_set(&set, &rules), Err(expected));
+        set.declarations = vec![(1, Declaration::Double(red(6), red(7)))];
+        let expected = InvalidSet {
+            reason: InvalidReason::DeclarationMismatch,
+            index: 1,
+        };
+        assert_eq!(validate_declared_set(&set, &rules), Err(expected));
+    }
+
+    #[test]
+    fn test_declared_set_invalid_without_declarations() {
+        let set = deserialize_declared_set("r5 j=r6 u7").unwrap();
+        let expected = InvalidSet {
+            reason: InvalidReason::ColorMismatchInRun,
+            index: 2,
+        };
+        assert_eq!(
+            validate_declared_set(&set, &RuleSet::special_jokers()),
+            Err(expected)
+        );
+    }
+
+    // SET POINTS
+
+    #[test]
+    fn test_points_run_with_joker() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 11)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        assert_eq!(set_points(&set, &RuleSet::standard()), Ok(18));
     }
 
     #[test]
-    fn test_invalid_run_with_double_joker_2() {
+    fn test_points_group_with_joker() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 11)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 11).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        assert_eq!(set_points(&set, &RuleSet::standard()), Ok(33));
     }
 
     #[test]
-    fn test_invalid_group_with_double_joker() {
+    fn test_points_double_joker() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        assert_eq!(set_points(&set, &RuleSet::special_jokers()), Ok(30));
     }
 
-    // MIRROR JOKER
-
     #[test]
-    fn test_valid_group_with_mirror() {
+    fn test_points_mirror() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 3).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 4).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 4).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        assert_eq!(set_points(&set, &RuleSet::special_jokers()), Ok(14));
     }
 
     #[test]
-    fn test_valid_run_with_mirror() {
+    fn test_points_color_change() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        assert_eq!(set_points(&set, &RuleSet::special_jokers()), Ok(18));
     }
 
     #[test]
-    fn test_invalid_group_with_mirror() {
+    fn test_points_undetermined() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
         ];
-        assert_eq!(is_valid_set(&set), false);
-    }
+        assert_eq!(set_points(&set, &RuleSet::standard()), Ok(27));
 
-    #[test]
-    fn test_invalid_run_with_mirror() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 8).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        assert_eq!(set_points(&set, &RuleSet::standard()), Ok(24));
     }
 
     #[test]
-    fn test_invalid_run_with_mirror_2() {
+    fn test_points_jokers_only() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        assert_eq!(set_points(&set, &RuleSet::special_jokers()), Ok(52));
     }
 
     #[test]
-    fn test_invalid_run_with_two_mirrors() {
+    fn test_points_invalid() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        assert!(set_points(&set, &RuleSet::standard()).is_err());
+
+        let rules = RuleSet {
+            min_set_size: 0,
+            ..RuleSet::standard()
+        };
+        assert!(set_points(&[], &rules).is_err());
     }
 
-    // COLOR CHANGE JOKER
+    // RULE SETS
 
     #[test]
-    fn test_valid_run_with_color_change() {
+    fn test_wrap_around_run() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 9)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 10)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 13).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let rules = RuleSet {
+            wrap_around: true,
+            ..RuleSet::standard()
+        };
+        let expected = SetKind::Run {
+            colors: vec![Some(TileColor::Red)],
+            start: Some(12),
+            end: Some(1),
+        };
+        assert_eq!(validate_set(&set, &rules), Ok(expected));
+        assert!(validate_set(&set, &RuleSet::standard()).is_err());
     }
 
     #[test]
-    fn test_invalid_run_with_color_change() {
+    fn test_wrap_around_run_with_jokers() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 9)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 10)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 1).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 3).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let rules = RuleSet {
+            wrap_around: true,
+            ..RuleSet::standard()
+        };
+        let expected = SetKind::Run {
+            colors: vec![Some(TileColor::Blue)],
+            start: Some(13),
+            end: Some(3),
+        };
+        assert_eq!(validate_set(&set, &rules), Ok(expected));
+        assert!(validate_set(&set, &RuleSet::standard()).is_err());
     }
 
     #[test]
-    fn test_invalid_run_with_color_change_without_number_skip() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 9)),
-        ];
-        assert_eq!(is_valid_set(&set), false);
+    fn test_wrap_around_run_longer_than_13() {
+        let mut set = Vec::new();
+        for value in 5..=13 {
+            set.push(Tile::Basic(
+                BasicTile::new(TileColor::Black, value).unwrap(),
+            ));
+        }
+        for value in 1..=5 {
+            set.push(Tile::Basic(
+                BasicTile::new(TileColor::Black, value).unwrap(),
+            ));
+        }
+        let rules = RuleSet {
+            wrap_around: true,
+            ..RuleSet::standard()
+        };
+        let expected = InvalidSet {
+            reason: InvalidReason::ValueOutOfRange,
+            index: 13,
+        };
+        assert_eq!(validate_set(&set, &rules), Err(expected));
     }
 
     #[test]
-    fn test_valid_run_with_two_color_changes() {
+    fn test_adjacent_color_changes_disallowed() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Orange, 6)),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 6).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
             Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 10)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let rules = RuleSet {
+            adjacent_color_changes: false,
+            ..RuleSet::special_jokers()
+        };
+        let expected = InvalidSet {
+            reason: InvalidReason::AdjacentColorChanges,
+            index: 2,
+        };
+        assert_eq!(validate_set(&set, &rules), Err(expected));
     }
 
     #[test]
-    fn test_valid_run_with_two_color_change_sandwich() {
+    fn test_special_joker_not_in_play() {
         let set = vec![
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = InvalidSet {
+            reason: InvalidReason::JokerNotAllowed,
+            index: 0,
+        };
+        assert_eq!(validate_set(&set, &RuleSet::standard()), Err(expected));
+
+        let rules = RuleSet {
+            double_jokers: true,
+            bag: TileBag::special_jokers(),
+            ..RuleSet::standard()
+        };
+        assert!(validate_set(&set, &rules).is_ok());
     }
 
     #[test]
-    fn test_valid_run_with_two_color_change_adjacent() {
+    fn test_min_set_size() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Orange, 6)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let rules = RuleSet {
+            min_set_size: 4,
+            ..RuleSet::standard()
+        };
+        let expected = InvalidSet {
+            reason: InvalidReason::TooShort,
+            index: 3,
+        };
+        assert_eq!(validate_set(&set, &rules), Err(expected));
     }
 
     #[test]
-    fn test_adjacent_color_change_house_rule() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Orange, 6)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 9)),
-        ];
-        assert_eq!(is_valid_set(&set), true);
+    fn test_empty_set() {
+        let rules = RuleSet {
+            min_set_size: 0,
+            ..RuleSet::standard()
+        };
+        let expected = InvalidSet {
+            reason: InvalidReason::TooShort,
+            index: 0,
+        };
+        assert_eq!(validate_set(&[], &rules), Err(expected));
+        assert_eq!(classify_set(&[], &rules), None);
     }
 
     #[test]
-    fn test_adjacent_color_change_house_rule_2() {
+    fn test_max_group_size() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Orange, 6)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Orange, 9)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let rules = RuleSet {
+            max_group_size: 3,
+            ..RuleSet::standard()
+        };
+        let expected = InvalidSet {
+            reason: InvalidReason::GroupTooLarge,
+            index: 3,
+        };
+        assert_eq!(validate_set(&set, &rules), Err(expected));
     }
 
-    // MIXED JOKERS
-
     #[test]
-    fn test_valid_mixed_1() {
+    fn test_max_group_size_rules_out_group() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let mut rules = RuleSet {
+            max_group_size: 3,
+            ..RuleSet::standard()
+        };
+        rules.bag.single_jokers = 3;
+        let expected = InvalidSet {
+            reason: InvalidReason::ValueOutOfRange,
+            index: 3,
+        };
+        assert_eq!(validate_set(&set, &rules), Err(expected));
     }
 
     #[test]
-    fn test_valid_mixed_2() {
+    fn test_more_copies_than_in_the_box() {
         let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Blue, 6)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 6)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        assert!(validate_set(&set, &RuleSet::special_jokers()).is_ok());
+
+        let mut rules = RuleSet::special_jokers();
+        rules.bag.mirror_jokers = 0;
+        let expected = InvalidSet {
+            reason: InvalidReason::TooManyCopies,
+            index: 2,
+        };
+        assert_eq!(validate_set(&set, &rules), Err(expected));
     }
 
+    // ARRANGEMENT
+
     #[test]
-    fn test_valid_mixed_3() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
+    fn test_arrange_run() {
+        let tiles = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+        ];
+        assert_eq!(
+            arrange_set(&tiles, &RuleSet::special_jokers()),
+            Some(expected)
+        );
     }
 
     #[test]
-    fn test_valid_mixed_4() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 9)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
+    fn test_arrange_group() {
+        let tiles = vec![
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        assert_eq!(
+            arrange_set(&tiles, &RuleSet::special_jokers()),
+            Some(expected)
+        );
+        assert_eq!(arrangements(&tiles, &RuleSet::special_jokers()).len(), 6);
     }
 
     #[test]
-    fn test_valid_mixed_5() {
-        let set = vec![
-            Tile::Joker(Joker::new(JokerVariant::Double)),
+    fn test_arrange_run_with_color_change() {
+        let tiles = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 10).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 3)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 10).unwrap()),
+        ];
+        assert_eq!(
+            arrange_set(&tiles, &RuleSet::special_jokers()),
+            Some(expected)
+        );
     }
 
     #[test]
-    fn test_valid_mixed_6() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 3)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Joker(Joker::new(JokerVariant::Single)),
+    fn test_arrange_mirror() {
+        let tiles = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 12)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        let expected = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+                Tile::Joker(Joker::new(JokerVariant::Mirror)),
+                Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+                Tile::Joker(Joker::new(JokerVariant::Single)),
+            ],
+            vec![
+                Tile::Joker(Joker::new(JokerVariant::Single)),
+                Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+                Tile::Joker(Joker::new(JokerVariant::Mirror)),
+                Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            ],
+        ];
+        assert_eq!(arrangements(&tiles, &RuleSet::special_jokers()), expected);
     }
 
     #[test]
-    fn test_invalid_mixed_7() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Joker(Joker::new(JokerVariant::Single)),
+    fn test_arrange_run_with_joker_at_either_end() {
+        let tiles = vec![
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), false);
+        let expected = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+                Tile::Joker(Joker::new(JokerVariant::Single)),
+            ],
+            vec![
+                Tile::Joker(Joker::new(JokerVariant::Single)),
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            ],
+        ];
+        assert_eq!(arrangements(&tiles, &RuleSet::special_jokers()), expected);
     }
 
     #[test]
-    fn test_valid_mixed_8() {
-        let set = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 5)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 6)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Joker(Joker::new(JokerVariant::Mirror)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 8)),
-            Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 5)),
+    fn test_arrange_invalid() {
+        let tiles = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
         ];
-        assert_eq!(is_valid_set(&set), true);
+        assert_eq!(arrange_set(&tiles, &RuleSet::special_jokers()), None);
+        assert!(arrangements(&tiles, &RuleSet::special_jokers()).is_empty());
     }
 }
@@ -0,0 +1,305 @@
+/// Copyright (c) 2020, Shoyo Inokuchi
+use crate::tiles::{deserialize_set, serialize_set, ParseError, Tile};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A position of the game as seen by the player to move.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct Position {
+    /// `board` stores the sets on the table, and `rack` the tiles of the player to move.
+    pub board: Vec<Vec<Tile>>,
+    pub rack: Vec<Tile>,
+    /// The tiles left to draw, if they are known.
+    pub pool: Option<Vec<Tile>>,
+    /// The racks of the other players in turn order, if they are known.
+    pub players: Vec<Vec<Tile>>,
+}
+
+impl Position {
+    pub fn new(board: Vec<Vec<Tile>>, rack: Vec<Tile>) -> Self {
+        Self {
+            board,
+            rack,
+            pool: None,
+            players: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serialize_position(self))
+    }
+}
+
+impl FromStr for Position {
+    type Err = PositionError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        deserialize_position(input)
+    }
+}
+
+/// An error found while reading position notation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionError {
+    /// The tiles written in a section could not be read.
+    Tiles(ParseError),
+    /// A line names a section that does not exist. `offset` is the byte offset of the name in the
+    /// input.
+    UnknownSection { offset: usize, name: String },
+    /// A section other than "player" appears more than once.
+    DuplicateSection { offset: usize, name: String },
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::Tiles(e) => write!(f, "{}", e),
+            PositionError::UnknownSection { offset, name } => {
+                write!(f, "unknown section \"{}\" at byte {}", name, offset)
+            }
+            PositionError::DuplicateSection { offset, name } => {
+                write!(f, "repeated section \"{}\" at byte {}", name, offset)
+            }
+        }
+    }
+}
+
+impl Error for PositionError {}
+
+impl From<ParseError> for PositionError {
+    fn from(error: ParseError) -> Self {
+        PositionError::Tiles(error)
+    }
+}
+
+/// The sections of the position notation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Board,
+    Rack,
+    Pool,
+    Player,
+}
+
+/// Convert a string describing a position and return the position. The notation extends the
+/// abbreviations of `deserialize_set` with sections:
+///
+/// Board: one set per line, or several sets on a line separated by "|". Lines before any other
+/// section belong to the board, which may also be introduced by "board:".
+/// Rack: "rack:" followed by the tiles of the player to move. The rack is empty if left out.
+/// Pool: "pool:" followed by the tiles left to draw.
+/// Players: "player:" followed by the rack of another player, once for every other player.
+///
+/// The tiles of a section may continue on the following lines, section names may be in either
+/// case, and everything after a "#" on a line is ignored.
+///
+/// Example:
+///     r1 r2 r3 | a5 u5 o5
+///     j a7 a8
+///     rack: r4 u6 j
+///     player: o1 o2
+pub fn deserialize_position(input: &str) -> Result<Position, PositionError> {
+    let mut position = Position::default();
+    let mut section = Section::Board;
+    let mut seen = Vec::new();
+    let mut start = 0;
+    for line in input.split('\n') {
+        let mut offset = start;
+        start += line.len() + 1;
+        let mut line = line.split('#').next().unwrap_or("");
+        if let Some(colon) = line.find(':') {
+            let name = line[..colon].trim();
+            let (at, name) = (offset + line.find(name).unwrap_or(0), name.to_string());
+            section = match _section(&name) {
+                Some(section) => section,
+                None => return Err(PositionError::UnknownSection { offset: at, name }),
+            };
+            if section != Section::Player && seen.contains(&section) {
+                return Err(PositionError::DuplicateSection { offset: at, name });
+            }
+            seen.push(section);
+            match section {
+                Section::Pool => position.pool = Some(Vec::new()),
+                Section::Player => position.players.push(Vec::new()),
+                Section::Board | Section::Rack => (),
+            }
+            offset += colon + 1;
+            line = &line[colon + 1..];
+        }
+        if section == Section::Board {
+            position.board.extend(_sets(line, offset)?);
+            continue;
+        }
+        let tiles = _set(line, offset)?;
+        match section {
+            Section::Rack => position.rack.extend(tiles),
+            Section::Pool => position.pool.get_or_insert_with(Vec::new).extend(tiles),
+            Section::Player => position.players.last_mut().unwrap().extend(tiles),
+            Section::Board => unreachable!(),
+        }
+    }
+    Ok(position)
+}
+
+/// Convert a position into the notation read by `deserialize_position`, with every set of the
+/// board on its own line followed by the rack, the pool if it is known and the other players.
+/// Deserializing the result gives back the same position, as long as no set on the board is empty:
+/// an empty set is written as an empty line, which holds no set.
+pub fn serialize_position(position: &Position) -> String {
+    let mut lines: Vec<String> = position
+        .board
+        .iter()
+        .map(|set| serialize_set(set))
+        .collect();
+    lines.push(_line("rack", &position.rack));
+    if let Some(pool) = &position.pool {
+        lines.push(_line("pool", pool));
+    }
+    for rack in &position.players {
+        lines.push(_line("player", rack));
+    }
+    lines.join("\n")
+}
+
+/// Return the section with the given name, in either case.
+fn _section(name: &str) -> Option<Section> {
+    match name.to_ascii_lowercase().as_str() {
+        "board" => Some(Section::Board),
+        "rack" => Some(Section::Rack),
+        "pool" => Some(Section::Pool),
+        "player" => Some(Section::Player),
+        _ => None,
+    }
+}
+
+/// Read the tiles written in a part of the input that starts at the given byte offset, and report
+/// errors at their offset in the whole input.
+pub(crate) fn _set(part: &str, offset: usize) -> Result<Vec<Tile>, ParseError> {
+    deserialize_set(part).map_err(|e| ParseError {
+        offset: offset + e.offset,
+        ..e
+    })
+}
+
+/// Read the sets written on a line that starts at the given byte offset of the input, separated
+/// by "|". Parts of the line without tiles are skipped.
+pub(crate) fn _sets(line: &str, mut offset: usize) -> Result<Vec<Vec<Tile>>, ParseError> {
+    let mut sets = Vec::new();
+    for part in line.split('|') {
+        let set = _set(part, offset)?;
+        offset += part.len() + 1;
+        if !set.is_empty() {
+            sets.push(set);
+        }
+    }
+    Ok(sets)
+}
+
+/// Return the line of a section holding the given tiles.
+fn _line(name: &str, tiles: &[Tile]) -> String {
+    if tiles.is_empty() {
+        format!("{}:", name)
+    } else {
+        format!("{}: {}", name, serialize_set(tiles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+    use crate::solve::can_win;
+    use crate::tiles::ParseErrorKind;
+
+    fn set(input: &str) -> Vec<Tile> {
+        deserialize_set(input).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize_position() {
+        let input = "r1 r2 r3 | a5 u5 o5\n\nj a7 a8\nrack: r4 u6\n  j # drawn last turn\n";
+        let expected = Position::new(
+            vec![set("r1 r2 r3"), set("a5 u5 o5"), set("j a7 a8")],
+            set("r4 u6 j"),
+        );
+        assert_eq!(deserialize_position(input), Ok(expected));
+        assert_eq!(deserialize_position(""), Ok(Position::default()));
+    }
+
+    #[test]
+    fn test_deserialize_position_sections() {
+        let input = "Rack: r4\nboard: r1 r2 r3\npool: o1 o2\nplayer: a1\nplayer:\nPLAYER: u2 u3";
+        let position = deserialize_position(input).unwrap();
+        assert_eq!(position.board, vec![set("r1 r2 r3")]);
+        assert_eq!(position.rack, set("r4"));
+        assert_eq!(position.pool, Some(set("o1 o2")));
+        assert_eq!(position.players, vec![set("a1"), vec![], set("u2 u3")]);
+    }
+
+    fn tile_error(input: &str) -> ParseError {
+        match deserialize_position(input) {
+            Err(PositionError::Tiles(error)) => error,
+            other => panic!("expected a tile error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_position_errors() {
+        let error = tile_error("r1 r2 r3\nrack: r4 x5");
+        assert_eq!(
+            (error.offset, error.kind),
+            (18, ParseErrorKind::UnknownTile)
+        );
+        assert_eq!(error.token, "x5");
+        let error = tile_error("r1 r2 | r3 r14");
+        assert_eq!(
+            (error.offset, error.kind),
+            (11, ParseErrorKind::InvalidValue)
+        );
+        let expected = PositionError::UnknownSection {
+            offset: 10,
+            name: "hand".to_string(),
+        };
+        assert_eq!(deserialize_position("r1 r2 r3\n hand: r4"), Err(expected));
+        let expected = PositionError::DuplicateSection {
+            offset: 9,
+            name: "rack".to_string(),
+        };
+        assert_eq!(deserialize_position("rack: r4\nrack: r5"), Err(expected));
+    }
+
+    #[test]
+    fn test_serialize_position_round_trip() {
+        let mut position = Position::new(vec![set("r1 r2 r3"), set("j a7 a8")], vec![]);
+        assert_eq!(position.to_string(), "r1 r2 r3\nj a7 a8\nrack:");
+        assert_eq!(position.to_string().parse(), Ok(position.clone()));
+        position.rack = set("m c");
+        position.pool = Some(vec![]);
+        position.players = vec![set("u1"), vec![]];
+        assert_eq!(
+            position.to_string(),
+            "r1 r2 r3\nj a7 a8\nrack: m c\npool:\nplayer: u1\nplayer:"
+        );
+        assert_eq!(position.to_string().parse(), Ok(position));
+    }
+
+    #[test]
+    fn test_serialize_position_drops_empty_sets() {
+        let position = Position::new(vec![vec![], set("r1 r2 r3")], vec![]);
+        assert_eq!(position.to_string(), "\nr1 r2 r3\nrack:");
+        let expected = Position::new(vec![set("r1 r2 r3")], vec![]);
+        assert_eq!(position.to_string().parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_position_can_win() {
+        let position: Position = "r1 r2 r3\nrack: r4 a4 u4 o4".parse().unwrap();
+        let rules = RuleSet::standard();
+        assert!(can_win(&position.board, &position.rack, &rules)
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,422 @@
+/// Copyright (c) 2020, Shoyo Inokuchi
+use crate::position::{_set, _sets};
+use crate::tiles::{serialize_set, ParseError, Tile};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The record of a complete game, from the deal to the last turn.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct GameRecord {
+    /// Named facts about the game, such as the players, the date, the rules and the seed the tiles
+    /// were shuffled with, in the order they are written. Names are made of letters, digits and
+    /// underscores.
+    pub tags: Vec<(String, String)>,
+    /// The racks dealt to the players at the start of the game, in turn order.
+    pub deals: Vec<Vec<Tile>>,
+    /// Every turn of the game, with the players taking turns in the order of `deals`.
+    pub turns: Vec<Turn>,
+}
+
+impl GameRecord {
+    /// Return the value of the first tag with the given name, if any.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Set the value of the tag with the given name, adding the tag after the others if it is new.
+    /// Return an error if the name is not made of letters, digits and underscores.
+    pub fn set_tag(&mut self, name: &str, value: &str) -> Result<(), RecordError> {
+        if !_tag_name(name) {
+            return Err(RecordError::InvalidTag {
+                offset: 0,
+                token: name.to_string(),
+            });
+        }
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Return the index of the player who took the given turn, or None if there are no players.
+    pub fn player(&self, turn: usize) -> Option<usize> {
+        if self.deals.is_empty() {
+            None
+        } else {
+            Some(turn % self.deals.len())
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serialize_record(self))
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        deserialize_record(input)
+    }
+}
+
+/// What a player did on their turn.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Turn {
+    /// The player drew a tile from the pool, which is None if the record does not say which.
+    Draw(Option<Tile>),
+    /// The player could neither play nor draw, since the pool was empty.
+    Pass,
+    /// The player played tiles from their rack, leaving the given sets on the board.
+    Play(Vec<Vec<Tile>>),
+}
+
+/// An error found while reading a game record. `offset` is the byte offset of the offending text
+/// in the input, and `token` the text as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    /// The tiles of a deal or a turn could not be read.
+    Tiles(ParseError),
+    /// A tag is not a name of letters, digits and underscores followed by a quoted value.
+    InvalidTag { offset: usize, token: String },
+    /// A turn is out of order or is not a draw, a pass or a play.
+    InvalidTurn { offset: usize, token: String },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::Tiles(e) => write!(f, "{}", e),
+            RecordError::InvalidTag { offset, token } => {
+                write!(f, "invalid tag \"{}\" at byte {}", token, offset)
+            }
+            RecordError::InvalidTurn { offset, token } => {
+                write!(f, "invalid turn \"{}\" at byte {}", token, offset)
+            }
+        }
+    }
+}
+
+impl Error for RecordError {}
+
+impl From<ParseError> for RecordError {
+    fn from(error: ParseError) -> Self {
+        RecordError::Tiles(error)
+    }
+}
+
+/// Convert a string holding a game record and return the record. The notation is made of lines of
+/// three kinds, built on the abbreviations of `deserialize_set`:
+///
+/// Tags: a name of letters, digits and underscores followed by a quoted value, in square brackets.
+/// Quotes and backslashes in the value are escaped with a backslash, and line breaks are written
+/// as `\n` and `\r`.
+/// Deals: "deal:" followed by the rack dealt to a player, once for every player in turn order.
+/// Turns: the number of the turn counting from 1, a period and then one of
+///     "draw", followed by the tile drawn if it is known
+///     "pass"
+///     "play", followed by the sets on the board after the play separated by "|"
+///
+/// Blank lines are skipped, and everything after a "#" on a line is ignored.
+///
+/// Example:
+///     [Players "Ann, Ben"]
+///     [Seed "42"]
+///     deal: r1 r2 r3 a7
+///     deal: u4 u9 o11 j
+///     1. play r1 r2 r3
+///     2. draw o2
+///     3. draw
+pub fn deserialize_record(input: &str) -> Result<GameRecord, RecordError> {
+    let mut record = GameRecord::default();
+    let mut start = 0;
+    for line in input.split('\n') {
+        let trimmed = line.trim_start();
+        let offset = start + line.len() - trimmed.len();
+        start += line.len() + 1;
+        if trimmed.starts_with('[') {
+            let tag = _tag(trimmed).ok_or_else(|| RecordError::InvalidTag {
+                offset,
+                token: trimmed.trim_end().to_string(),
+            })?;
+            record.tags.push(tag);
+            continue;
+        }
+        let line = trimmed.split('#').next().unwrap_or("").trim_end();
+        if line.is_empty() {
+            continue;
+        } else if line.to_ascii_lowercase().starts_with("deal:") {
+            record.deals.push(_set(&line[5..], offset + 5)?);
+        } else {
+            let turn = _turn(line, offset, record.turns.len() + 1)?;
+            record.turns.push(turn);
+        }
+    }
+    Ok(record)
+}
+
+/// Convert a game record into the notation read by `deserialize_record`, with the tags, the deals
+/// and the turns separated by blank lines. Deserializing the result gives back the same record, as
+/// long as every tag name is made of letters, digits and underscores and no play leaves an empty
+/// set on the board.
+pub fn serialize_record(record: &GameRecord) -> String {
+    let tags: Vec<String> = record
+        .tags
+        .iter()
+        .map(|(name, value)| format!("[{} \"{}\"]", name, _escape(value)))
+        .collect();
+    let deals: Vec<String> = record
+        .deals
+        .iter()
+        .map(|rack| {
+            format!("deal: {}", serialize_set(rack))
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+    let turns: Vec<String> = record
+        .turns
+        .iter()
+        .enumerate()
+        .map(|(index, turn)| {
+            format!("{}. {}", index + 1, _action(turn))
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+    let mut output = [tags, deals, turns]
+        .iter()
+        .filter(|lines| !lines.is_empty())
+        .map(|lines| lines.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    output.push('\n');
+    output
+}
+
+/// Read a tag line such as `[Seed "42"]`, or return None if the line is not a tag. The value may
+/// contain a "#", so the line is read before comments are removed.
+fn _tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?;
+    let quote = inner.find('"')?;
+    let name = inner[..quote].trim();
+    if !_tag_name(name) {
+        return None;
+    }
+    let mut value = String::new();
+    let mut chars = inner[quote + 1..].chars();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    let rest = chars.as_str().trim_start().strip_prefix(']')?.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Some((name.to_string(), value))
+    } else {
+        None
+    }
+}
+
+/// Read a turn line such as "2. draw o2", which must carry the given number.
+fn _turn(line: &str, offset: usize, number: usize) -> Result<Turn, RecordError> {
+    let error = |at: usize, token: &str| RecordError::InvalidTurn {
+        offset: offset + at,
+        token: token.to_string(),
+    };
+    let period = line.find('.').ok_or_else(|| error(0, line))?;
+    if line[..period].trim().parse() != Ok(number) {
+        return Err(error(0, &line[..=period]));
+    }
+    let rest = &line[period + 1..];
+    let action = rest.trim_start();
+    let at = period + 1 + rest.len() - action.len();
+    let end = action.find(char::is_whitespace).unwrap_or(action.len());
+    let (name, tiles) = action.split_at(end);
+    let at_tiles = offset + at + end;
+    match name.to_ascii_lowercase().as_str() {
+        "draw" => match _set(tiles, at_tiles)?.as_slice() {
+            [] => Ok(Turn::Draw(None)),
+            &[tile] => Ok(Turn::Draw(Some(tile))),
+            _ => {
+                let drawn = tiles.trim_start();
+                Err(error(at + end + tiles.len() - drawn.len(), drawn))
+            }
+        },
+        "pass" if tiles.trim().is_empty() => Ok(Turn::Pass),
+        "play" => Ok(Turn::Play(_sets(tiles, at_tiles)?)),
+        _ => Err(error(at, action)),
+    }
+}
+
+/// Return the action of a turn as written after its number.
+fn _action(turn: &Turn) -> String {
+    match turn {
+        Turn::Draw(Some(tile)) => format!("draw {}", tile),
+        Turn::Draw(None) => "draw".to_string(),
+        Turn::Pass => "pass".to_string(),
+        Turn::Play(board) => {
+            let sets: Vec<String> = board.iter().map(|set| serialize_set(set)).collect();
+            format!("play {}", sets.join(" | "))
+        }
+    }
+}
+
+/// Return whether a tag name is made of letters, digits and underscores.
+fn _tag_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Escape the quotes, backslashes and line breaks of a tag value.
+fn _escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiles::{deserialize_set, ParseErrorKind};
+
+    fn set(input: &str) -> Vec<Tile> {
+        deserialize_set(input).unwrap()
+    }
+
+    fn game() -> GameRecord {
+        GameRecord {
+            tags: vec![
+                ("Players".to_string(), "Ann, Ben".to_string()),
+                ("Date".to_string(), "2020-05-01".to_string()),
+                ("Rules".to_string(), "standard".to_string()),
+                ("Seed".to_string(), "42".to_string()),
+            ],
+            deals: vec![set("r1 r2 r3 a7 o9"), set("u4 u9 o11 j")],
+            turns: vec![
+                Turn::Play(vec![set("r1 r2 r3")]),
+                Turn::Draw(Some(set("o2")[0])),
+                Turn::Draw(None),
+                Turn::Play(vec![set("r1 r2 r3"), set("u9 j o9")]),
+                Turn::Pass,
+            ],
+        }
+    }
+
+    const GAME: &str = "[Players \"Ann, Ben\"]
+[Date \"2020-05-01\"]
+[Rules \"standard\"]
+[Seed \"42\"]
+
+deal: r1 r2 r3 a7 o9
+deal: u4 u9 o11 j
+
+1. play r1 r2 r3
+2. draw o2
+3. draw
+4. play r1 r2 r3 | u9 j o9
+5. pass
+";
+
+    #[test]
+    fn test_serialize_record() {
+        assert_eq!(serialize_record(&game()), GAME);
+        assert_eq!(serialize_record(&GameRecord::default()), "\n");
+    }
+
+    #[test]
+    fn test_deserialize_record() {
+        assert_eq!(deserialize_record(GAME), Ok(game()));
+        let input = "  [Seed  \"7\" ]  # shuffled twice\n\nDEAL: r5\n1.PLAY r5 r6 r7|a1 u1 o1\n";
+        let record = deserialize_record(input).unwrap();
+        assert_eq!(record.tag("Seed"), Some("7"));
+        assert_eq!(record.deals, vec![set("r5")]);
+        let board = vec![set("r5 r6 r7"), set("a1 u1 o1")];
+        assert_eq!(record.turns, vec![Turn::Play(board)]);
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let mut record = game();
+        let event = "The \"final\" \\ round #3\nplayed twice\r\n\\n";
+        record.set_tag("Event", event).unwrap();
+        record.set_tag("Seed", "43").unwrap();
+        record.deals.push(vec![]);
+        let text = record.to_string();
+        assert_eq!(text.parse(), Ok(record.clone()));
+        assert_eq!(text.parse::<GameRecord>().unwrap().tag("Seed"), Some("43"));
+        assert_eq!(record.tag("Event"), Some(event));
+        for name in &["", "Round two", "Round\"", "Seed]"] {
+            let expected = RecordError::InvalidTag {
+                offset: 0,
+                token: name.to_string(),
+            };
+            assert_eq!(record.set_tag(name, "1"), Err(expected));
+        }
+        assert_eq!(record.tags.len(), 5);
+        assert_eq!(record.player(4), Some(1));
+    }
+
+    #[test]
+    fn test_play_drops_empty_sets() {
+        let mut record = GameRecord::default();
+        record.turns.push(Turn::Play(vec![vec![], set("r1 r2 r3")]));
+        assert_eq!(record.to_string(), "1. play  | r1 r2 r3\n");
+        let expected = vec![Turn::Play(vec![set("r1 r2 r3")])];
+        assert_eq!(
+            record.to_string().parse::<GameRecord>().unwrap().turns,
+            expected
+        );
+    }
+
+    fn tag_error(offset: usize, token: &str) -> Result<GameRecord, RecordError> {
+        let token = token.to_string();
+        Err(RecordError::InvalidTag { offset, token })
+    }
+
+    fn turn_error(offset: usize, token: &str) -> Result<GameRecord, RecordError> {
+        let token = token.to_string();
+        Err(RecordError::InvalidTurn { offset, token })
+    }
+
+    fn tile_error(input: &str) -> ParseError {
+        match deserialize_record(input) {
+            Err(RecordError::Tiles(error)) => error,
+            other => panic!("expected a tile error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_record_errors() {
+        assert_eq!(deserialize_record("[Seed 42]"), tag_error(0, "[Seed 42]"));
+        let input = "\n [Seed \"42\"] r5";
+        assert_eq!(deserialize_record(input), tag_error(2, "[Seed \"42\"] r5"));
+        assert_eq!(deserialize_record("1. draw\n3. pass"), turn_error(8, "3."));
+        assert_eq!(deserialize_record("1. draw r5 r6"), turn_error(8, "r5 r6"));
+        assert_eq!(deserialize_record("1. take r5"), turn_error(3, "take r5"));
+        let error = tile_error("1. play r1 r2 | r3 x4");
+        assert_eq!(
+            (error.offset, error.kind),
+            (19, ParseErrorKind::UnknownTile)
+        );
+        let error = tile_error("deal: r1 r14");
+        assert_eq!(
+            (error.offset, error.kind),
+            (9, ParseErrorKind::InvalidValue)
+        );
+    }
+}
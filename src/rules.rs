@@ -0,0 +1,80 @@
+/// Copyright (c) 2020, Shoyo Inokuchi
+use crate::tiles::{JokerVariant, Penalties, TileBag};
+
+/// House rules that decide which sequences of tiles form valid sets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSet {
+    /// `double_jokers`, `mirror_jokers` and `color_change_jokers` toggle whether each kind of
+    /// special joker may be played. Single jokers are always allowed.
+    pub double_jokers: bool,
+    pub mirror_jokers: bool,
+    pub color_change_jokers: bool,
+
+    /// `wrap_around` allows runs to continue from 13 back to 1.
+    /// Ex. 12 13 1 .. is only valid with `wrap_around`
+    pub wrap_around: bool,
+
+    /// `adjacent_color_changes` allows two color-change jokers to be placed next to each other.
+    /// Ex. 6 C C 9 .. is only valid with `adjacent_color_changes`
+    pub adjacent_color_changes: bool,
+
+    /// `min_set_size` is the minimum number of tiles in a set. A double joker counts as one tile.
+    /// A set always needs at least one tile, even when this is 0.
+    pub min_set_size: u8,
+
+    /// `max_group_size` is the maximum length of a group. Groups can never be longer than 4, since
+    /// every tile in a group must have a different color.
+    pub max_group_size: u8,
+
+    /// `initial_meld` is the number of points a player must lay down in new sets from their own
+    /// rack before they may play on the board.
+    pub initial_meld: u32,
+
+    /// `penalties` gives the points that tiles left on a rack cost at the end of a round. The
+    /// solver also uses them as the points of jokers played from the rack.
+    pub penalties: Penalties,
+
+    /// `bag` describes the tiles in the box. No set, board or rack may use more copies of a tile
+    /// than the box contains.
+    pub bag: TileBag,
+}
+
+impl RuleSet {
+    /// The rules of the original game, played with single jokers only.
+    pub fn standard() -> Self {
+        Self {
+            double_jokers: false,
+            mirror_jokers: false,
+            color_change_jokers: false,
+            wrap_around: false,
+            adjacent_color_changes: false,
+            min_set_size: 3,
+            max_group_size: 4,
+            initial_meld: 30,
+            penalties: Penalties::standard(),
+            bag: TileBag::standard(),
+        }
+    }
+
+    /// The rules of the special jokers edition, in which every special joker is in play.
+    pub fn special_jokers() -> Self {
+        Self {
+            double_jokers: true,
+            mirror_jokers: true,
+            color_change_jokers: true,
+            adjacent_color_changes: true,
+            bag: TileBag::special_jokers(),
+            ..Self::standard()
+        }
+    }
+
+    /// Return whether jokers of the given variant may be played.
+    pub fn allows(&self, variant: &JokerVariant) -> bool {
+        match *variant {
+            JokerVariant::Single => true,
+            JokerVariant::Double => self.double_jokers,
+            JokerVariant::Mirror => self.mirror_jokers,
+            JokerVariant::ColorChange => self.color_change_jokers,
+        }
+    }
+}
@@ -0,0 +1,676 @@
+/// Copyright (c) 2020, Shoyo Inokuchi
+use crate::tiles::{Tile, TileMultiset};
+use std::fmt;
+
+/// A physical operation on the table, as a player would carry it out by hand. Sets are numbered by
+/// their index on the table just before the operation. New sets are always laid at the end of the
+/// table, and a set is cleared from the table as soon as its last tile is taken.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Split the set before the tile at `position`, moving the tiles from there on into a new set.
+    Split { set: usize, position: usize },
+    /// Join the `second` set onto the end of the `first` one.
+    Join { first: usize, second: usize },
+    /// Take the tile at `position` out of the `from` set and put it down at `to`. When both are the
+    /// same set, `to` counts positions with the tile already taken out.
+    Take {
+        tile: Tile,
+        from: usize,
+        position: usize,
+        to: Place,
+    },
+    /// Put down a tile from the rack at `to`.
+    Insert { tile: Tile, to: Place },
+    /// Put down a tile from the rack in place of the joker at `position` of the set, and set the
+    /// joker aside in a new set of its own until it is played again.
+    ReplaceJoker {
+        set: usize,
+        position: usize,
+        tile: Tile,
+    },
+}
+
+/// Where a tile is put down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Place {
+    /// Before the tile at `position` of the set, or at its end if `position` is its length.
+    Into { set: usize, position: usize },
+    /// In a new set of its own.
+    NewSet,
+}
+
+impl Operation {
+    /// Carry out the operation on the table. Panics if the operation refers to a set or a position
+    /// that is not on the table.
+    pub fn apply(&self, table: &mut Vec<Vec<Tile>>) {
+        self._apply(table, Tile::clone);
+    }
+
+    /// Carry out the operation on any table of items kept in step with the tiles, making the items
+    /// of tiles coming from the rack with `make`.
+    fn _apply<T, F>(&self, table: &mut Vec<Vec<T>>, make: F)
+    where
+        F: FnOnce(&Tile) -> T,
+    {
+        match *self {
+            Operation::Split { set, position } => {
+                let tail = table[set].split_off(position);
+                table.push(tail);
+            }
+            Operation::Join { first, second } => {
+                let tail = std::mem::take(&mut table[second]);
+                table[first].extend(tail);
+                table.remove(second);
+            }
+            Operation::Take {
+                from, position, to, ..
+            } => {
+                let item = table[from].remove(position);
+                _put(table, to, item);
+                if table[from].is_empty() {
+                    table.remove(from);
+                }
+            }
+            Operation::Insert { ref tile, to } => _put(table, to, make(tile)),
+            Operation::ReplaceJoker {
+                set,
+                position,
+                ref tile,
+            } => {
+                let joker = std::mem::replace(&mut table[set][position], make(tile));
+                table.push(vec![joker]);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operation::Split { set, position } => write!(
+                f,
+                "split set {} before position {}, moving the tiles from there on into a new set",
+                set + 1,
+                position + 1
+            ),
+            Operation::Join { first, second } => {
+                write!(
+                    f,
+                    "join set {} onto the end of set {}",
+                    second + 1,
+                    first + 1
+                )
+            }
+            Operation::Take {
+                tile,
+                from,
+                position,
+                to,
+            } => write!(
+                f,
+                "take the {} at position {} of set {} and put it {}",
+                _name(tile),
+                position + 1,
+                from + 1,
+                to
+            ),
+            Operation::Insert { tile, to } => {
+                write!(f, "put the {} from the rack {}", _name(tile), to)
+            }
+            Operation::ReplaceJoker {
+                set,
+                position,
+                tile,
+            } => write!(
+                f,
+                "replace the joker at position {} of set {} with the {} from the rack, and set \
+                 the joker aside",
+                position + 1,
+                set + 1,
+                _name(tile)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Place::Into { set, position } => {
+                write!(f, "at position {} of set {}", position + 1, set + 1)
+            }
+            Place::NewSet => write!(f, "in a new set"),
+        }
+    }
+}
+
+/// Given the board before and after a move, return the operations that turn one into the other,
+/// playing the tiles of the after board that are not on the before board from the rack. Return
+/// None if the after board leaves out some tile of the before board, or if either board holds a
+/// basic tile with an invalid value.
+///
+/// Carrying out the operations in order leaves exactly the sets of the after board on the table,
+/// though not necessarily in the same order. The sets in between need not be valid.
+pub fn script(before: &[Vec<Tile>], after: &[Vec<Tile>]) -> Option<Vec<Operation>> {
+    let have = TileMultiset::from_tiles(before.iter().flatten()).ok()?;
+    let want = TileMultiset::from_tiles(after.iter().flatten()).ok()?;
+    if !have.is_subset(&want) {
+        return None;
+    }
+    let mut script = Script {
+        table: before.to_vec(),
+        marks: _destinations(before, after),
+        homes: vec![None; after.len()],
+        operations: Vec::new(),
+    };
+    script.split();
+    for (set, target) in after.iter().enumerate() {
+        script.build(set, target);
+    }
+    Some(script.operations)
+}
+
+/// Where a tile on the table is headed.
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    /// `set` is the index of the set of the after board that the tile ends up in.
+    set: usize,
+    /// `index` is the position of the tile within that set, once the tile has been put in place.
+    index: Option<usize>,
+}
+
+/// Builder of the operations turning one board into another.
+///
+/// Implementation:
+/// Every tile of the before board is first given the set of the after board it ends up in, with
+/// the sets sharing the most tiles paired first. Every before set is then split into the stretches
+/// of two or more tiles headed for the same set, and the after sets are built one at a time: the
+/// largest stretch headed for the set becomes its home, other stretches that fit at either end are
+/// joined onto it, and the missing tiles are moved in one by one, from the table or the rack.
+/// Tiles in a home that are headed elsewhere stay where they are until their own set is built.
+struct Script {
+    table: Vec<Vec<Tile>>,
+    marks: Vec<Vec<Mark>>,
+
+    /// `homes` stores the index on the table of every after set, once it has been started.
+    homes: Vec<Option<usize>>,
+
+    operations: Vec<Operation>,
+}
+
+impl Script {
+    /// Carry out the operation on the table, giving the tile it puts down the given mark.
+    fn run(&mut self, operation: Operation, mark: Mark) {
+        let removed = match operation {
+            Operation::Join { second, .. } => Some(second),
+            Operation::Take { from, .. } if self.table[from].len() == 1 => Some(from),
+            _ => None,
+        };
+        if let Operation::Take { from, position, .. } = operation {
+            self.marks[from][position] = mark;
+        }
+        operation.apply(&mut self.table);
+        operation._apply(&mut self.marks, |_| mark);
+        for home in self.homes.iter_mut().flatten() {
+            if let Operation::Join { first, second } = operation {
+                if *home == second {
+                    *home = first;
+                }
+            }
+            if matches!(removed, Some(removed) if *home > removed) {
+                *home -= 1;
+            }
+        }
+        self.operations.push(operation);
+    }
+
+    /// Split every set of the table into its stretches of two or more tiles headed for the same
+    /// set. Single tiles headed elsewhere stay with the stretch before them.
+    fn split(&mut self) {
+        for set in 0..self.table.len() {
+            let marks = &self.marks[set];
+            let mut cuts = Vec::new();
+            let mut start = 0;
+            while start < marks.len() {
+                let end = (start..marks.len())
+                    .find(|&k| marks[k].set != marks[start].set)
+                    .unwrap_or(marks.len());
+                if end - start >= 2 {
+                    cuts.push(start);
+                }
+                start = end;
+            }
+            for &position in cuts.iter().skip(1).rev() {
+                self.run(Operation::Split { set, position }, _mark(set));
+            }
+        }
+    }
+
+    /// Lay out the after set with the given index on the table.
+    fn build(&mut self, set: usize, target: &[Tile]) {
+        let mut pieces: Vec<usize> = (0..self.table.len())
+            .filter(|&piece| !self.homes.contains(&Some(piece)))
+            .filter(|&piece| self._stretch(piece, set).1 >= 2)
+            .collect();
+        pieces.sort_by_key(|&piece| std::cmp::Reverse(self._stretch(piece, set)));
+        if let Some((&home, others)) = pieces.split_first() {
+            self.homes[set] = Some(home);
+            self.claim(home, set, target, 0..target.len());
+            let mut others = others.to_vec();
+            while !others.is_empty() {
+                let piece = others.remove(0);
+                if let Some(removed) = self.join(piece, set, target) {
+                    for other in others.iter_mut().filter(|other| **other > removed) {
+                        *other -= 1;
+                    }
+                }
+            }
+        }
+        for (index, tile) in target.iter().enumerate() {
+            if !self._placed(set, index) {
+                self.place(set, index, tile);
+            }
+        }
+    }
+
+    /// Join the piece onto the home of the set if its tiles fit at either end, and return the index
+    /// of the set cleared from the table. Otherwise its tiles are left to be moved in one by one.
+    fn join(&mut self, piece: usize, set: usize, target: &[Tile]) -> Option<usize> {
+        let home = self.homes[set].unwrap();
+        let placed: Vec<usize> = self.marks[home]
+            .iter()
+            .filter(|mark| mark.set == set)
+            .filter_map(|mark| mark.index)
+            .collect();
+        let (low, high) = match (placed.iter().min(), placed.iter().max()) {
+            (Some(&low), Some(&high)) => (low, high),
+            _ => (target.len(), 0),
+        };
+        if self._fits(piece, set, target, high + 1..target.len()) {
+            self.claim(piece, set, target, high + 1..target.len());
+            self.run(
+                Operation::Join {
+                    first: home,
+                    second: piece,
+                },
+                _mark(set),
+            );
+            Some(piece)
+        } else if self._fits(piece, set, target, 0..low) {
+            self.claim(piece, set, target, 0..low);
+            self.run(
+                Operation::Join {
+                    first: piece,
+                    second: home,
+                },
+                _mark(set),
+            );
+            Some(home)
+        } else {
+            None
+        }
+    }
+
+    /// Put the tile at the given index of the set in place, taking it from the table if a tile
+    /// headed for the set is still loose, or from the rack otherwise.
+    fn place(&mut self, set: usize, index: usize, tile: &Tile) {
+        let mark = Mark {
+            set,
+            index: Some(index),
+        };
+        let source = (0..self.table.len()).find_map(|from| {
+            (0..self.table[from].len())
+                .find(|&k| {
+                    self.marks[from][k].set == set
+                        && self.marks[from][k].index.is_none()
+                        && self.table[from][k] == *tile
+                })
+                .map(|k| (from, k))
+        });
+        let home = match self.homes[set] {
+            Some(home) => home,
+            None => {
+                let operation = match source {
+                    Some((from, position)) => Operation::Take {
+                        tile: *tile,
+                        from,
+                        position,
+                        to: Place::NewSet,
+                    },
+                    None => Operation::Insert {
+                        tile: *tile,
+                        to: Place::NewSet,
+                    },
+                };
+                self.run(operation, mark);
+                self.homes[set] = Some(self.table.len() - 1);
+                return;
+            }
+        };
+        let mut position = self.marks[home]
+            .iter()
+            .rposition(|m| m.set == set && matches!(m.index, Some(i) if i < index))
+            .map_or(0, |k| k + 1);
+        let operation = match source {
+            Some((from, k)) if from == home && k == position => {
+                self.marks[home][k] = mark;
+                return;
+            }
+            Some((from, k)) => {
+                if from == home && k < position {
+                    position -= 1;
+                }
+                Operation::Take {
+                    tile: *tile,
+                    from,
+                    position: k,
+                    to: Place::Into {
+                        set: home,
+                        position,
+                    },
+                }
+            }
+            None if self._replaces_joker(home, position, set, index) => Operation::ReplaceJoker {
+                set: home,
+                position,
+                tile: *tile,
+            },
+            None => Operation::Insert {
+                tile: *tile,
+                to: Place::Into {
+                    set: home,
+                    position,
+                },
+            },
+        };
+        self.run(operation, mark);
+    }
+
+    /// Match the tiles of the piece headed for the set, in order, with the tiles at the given
+    /// indices of the set that are not yet in place.
+    fn claim(&mut self, piece: usize, set: usize, target: &[Tile], range: std::ops::Range<usize>) {
+        let free: Vec<usize> = range.filter(|&index| !self._placed(set, index)).collect();
+        let mut indices = free.into_iter();
+        let mut next = indices.next();
+        for k in 0..self.table[piece].len() {
+            if self.marks[piece][k].set != set {
+                continue;
+            }
+            while matches!(next, Some(index) if target[index] != self.table[piece][k]) {
+                next = indices.next();
+            }
+            if let Some(index) = next {
+                self.marks[piece][k].index = Some(index);
+                next = indices.next();
+            }
+        }
+    }
+
+    /// Return the length of the longest stretch of tiles of the piece headed for the set, and the
+    /// number of its tiles headed for the set.
+    fn _stretch(&self, piece: usize, set: usize) -> (usize, usize) {
+        let marks = &self.marks[piece];
+        let longest = marks
+            .split(|mark| mark.set != set)
+            .map(<[Mark]>::len)
+            .max()
+            .unwrap_or(0);
+        (longest, marks.iter().filter(|mark| mark.set == set).count())
+    }
+
+    /// Return whether every tile of the piece headed for the set can be matched, in order, with
+    /// the tiles at the given indices of the set that are not yet in place.
+    fn _fits(
+        &self,
+        piece: usize,
+        set: usize,
+        target: &[Tile],
+        range: std::ops::Range<usize>,
+    ) -> bool {
+        let mut indices = range.filter(|&index| !self._placed(set, index));
+        (0..self.table[piece].len())
+            .filter(|&k| self.marks[piece][k].set == set)
+            .all(|k| indices.any(|index| target[index] == self.table[piece][k]))
+    }
+
+    /// Return whether the tile at the given index of the set is already in place.
+    fn _placed(&self, set: usize, index: usize) -> bool {
+        match self.homes[set] {
+            Some(home) => self.marks[home]
+                .iter()
+                .any(|mark| mark.set == set && mark.index == Some(index)),
+            None => false,
+        }
+    }
+
+    /// Return whether the tile at the given index of the set can take the place of a joker headed
+    /// elsewhere, sitting alone between its neighbours in the home.
+    fn _replaces_joker(&self, home: usize, position: usize, set: usize, index: usize) -> bool {
+        let marks = &self.marks[home];
+        position < marks.len()
+            && matches!(self.table[home][position], Tile::Joker(_))
+            && marks[position].set != set
+            && marks
+                .get(position + 1)
+                .is_none_or(|next| next.set == set && matches!(next.index, Some(i) if i > index))
+    }
+}
+
+/// Return the mark of a tile that is not moved by the operation.
+fn _mark(set: usize) -> Mark {
+    Mark { set, index: None }
+}
+
+/// Return the set of the after board that every tile of the before board ends up in. Sets sharing
+/// the most tiles are paired first, and copies of a tile are handed out from left to right.
+fn _destinations(before: &[Vec<Tile>], after: &[Vec<Tile>]) -> Vec<Vec<Mark>> {
+    let mut left: Vec<TileMultiset> = after
+        .iter()
+        .map(|set| TileMultiset::from_tiles(set).unwrap())
+        .collect();
+    let mut pairs = Vec::new();
+    for (i, old) in before.iter().enumerate() {
+        let counts = TileMultiset::from_tiles(old).unwrap();
+        for (j, new) in left.iter().enumerate() {
+            let shared = counts.intersection(new).len();
+            if shared > 0 {
+                pairs.push((shared, i, j));
+            }
+        }
+    }
+    pairs.sort_by_key(|&(shared, i, j)| (std::cmp::Reverse(shared), i, j));
+    let mut marks: Vec<Vec<Option<usize>>> =
+        before.iter().map(|set| vec![None; set.len()]).collect();
+    for (_, i, j) in pairs {
+        for (k, tile) in before[i].iter().enumerate() {
+            if marks[i][k].is_none() && left[j].remove(tile) {
+                marks[i][k] = Some(j);
+            }
+        }
+    }
+    marks
+        .into_iter()
+        .map(|set| set.into_iter().map(|j| _mark(j.unwrap())).collect())
+        .collect()
+}
+
+/// Put the item down at the given place of the table.
+fn _put<T>(table: &mut Vec<Vec<T>>, to: Place, item: T) {
+    match to {
+        Place::Into { set, position } => table[set].insert(position, item),
+        Place::NewSet => table.push(vec![item]),
+    }
+}
+
+/// Return the name of the tile as written in the operations.
+fn _name(tile: &Tile) -> String {
+    match tile {
+        Tile::Basic(t) => format!("{} {}", t.color, t.value),
+        Tile::Joker(j) => format!("{} JOKER", j.variant),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiles::{BasicTile, Joker, JokerVariant, TileColor};
+
+    fn red(value: u8) -> Tile {
+        Tile::Basic(BasicTile::new(TileColor::Red, value).unwrap())
+    }
+
+    /// Check that carrying out the operations turns the before board into the after board.
+    fn assert_replays(before: &[Vec<Tile>], after: &[Vec<Tile>], operations: &[Operation]) {
+        let mut table = before.to_vec();
+        for operation in operations {
+            operation.apply(&mut table);
+        }
+        let mut expected = after.to_vec();
+        for board in [&mut table, &mut expected].iter_mut() {
+            board.sort();
+        }
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_script_extends_set() {
+        let before = vec![vec![red(1), red(2), red(3)]];
+        let after = vec![vec![red(1), red(2), red(3), red(4)]];
+        let operations = script(&before, &after).unwrap();
+        assert_eq!(
+            operations,
+            vec![Operation::Insert {
+                tile: red(4),
+                to: Place::Into {
+                    set: 0,
+                    position: 3
+                },
+            }]
+        );
+        assert_replays(&before, &after, &operations);
+    }
+
+    #[test]
+    fn test_script_splits_run() {
+        let before = vec![vec![red(4), red(5), red(6), red(7), red(8), red(9)]];
+        let after = vec![vec![red(4), red(5), red(6)], vec![red(7), red(8), red(9)]];
+        let operations = script(&before, &after).unwrap();
+        assert_eq!(
+            operations,
+            vec![Operation::Split {
+                set: 0,
+                position: 3
+            }]
+        );
+        assert_replays(&before, &after, &operations);
+    }
+
+    #[test]
+    fn test_script_joins_sets() {
+        let before = vec![vec![red(4), red(5), red(6)], vec![red(1), red(2), red(3)]];
+        let after = vec![vec![red(1), red(2), red(3), red(4), red(5), red(6)]];
+        let operations = script(&before, &after).unwrap();
+        assert_eq!(
+            operations,
+            vec![Operation::Join {
+                first: 1,
+                second: 0
+            }]
+        );
+        assert_replays(&before, &after, &operations);
+    }
+
+    #[test]
+    fn test_script_takes_tile() {
+        let blue = Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap());
+        let orange = Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap());
+        let before = vec![vec![red(4), red(5), red(6), red(7)]];
+        let after = vec![vec![red(4), red(5), red(6)], vec![red(7), blue, orange]];
+        let operations = script(&before, &after).unwrap();
+        assert_eq!(
+            operations,
+            vec![
+                Operation::Take {
+                    tile: red(7),
+                    from: 0,
+                    position: 3,
+                    to: Place::NewSet,
+                },
+                Operation::Insert {
+                    tile: blue,
+                    to: Place::Into {
+                        set: 1,
+                        position: 1
+                    },
+                },
+                Operation::Insert {
+                    tile: orange,
+                    to: Place::Into {
+                        set: 1,
+                        position: 2
+                    },
+                },
+            ]
+        );
+        assert_replays(&before, &after, &operations);
+    }
+
+    #[test]
+    fn test_script_replaces_joker() {
+        let joker = Tile::Joker(Joker::new(JokerVariant::Single));
+        let blue = Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap());
+        let orange = Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap());
+        let before = vec![vec![red(4), joker, red(6)]];
+        let after = vec![vec![red(4), red(5), red(6)], vec![blue, orange, joker]];
+        let operations = script(&before, &after).unwrap();
+        assert_eq!(
+            operations[0],
+            Operation::ReplaceJoker {
+                set: 0,
+                position: 1,
+                tile: red(5),
+            }
+        );
+        assert_replays(&before, &after, &operations);
+    }
+
+    #[test]
+    fn test_script_reorders_set() {
+        let before = vec![vec![red(3), red(1), red(2)]];
+        let after = vec![vec![red(1), red(2), red(3)]];
+        let operations = script(&before, &after).unwrap();
+        assert_replays(&before, &after, &operations);
+    }
+
+    #[test]
+    fn test_script_rejects_missing_tile() {
+        let before = vec![vec![red(1), red(2), red(3), red(4)]];
+        let after = vec![vec![red(1), red(2), red(3)]];
+        assert_eq!(script(&before, &after), None);
+    }
+
+    #[test]
+    fn test_script_rejects_invalid_tile() {
+        let zero = Tile::Basic(BasicTile {
+            color: TileColor::Red,
+            value: 0,
+        });
+        let before = vec![vec![red(1), red(2), red(3)]];
+        let after = vec![vec![zero, red(1), red(2), red(3)]];
+        assert_eq!(script(&before, &after), None);
+    }
+
+    #[test]
+    fn test_describe_operation() {
+        let operation = Operation::ReplaceJoker {
+            set: 0,
+            position: 1,
+            tile: red(5),
+        };
+        assert_eq!(
+            operation.to_string(),
+            "replace the joker at position 2 of set 1 with the RED 5 from the rack, and set the \
+             joker aside"
+        );
+    }
+}
@@ -1,12 +1,2810 @@
 /// Copyright (c) 2020, Shoyo Inokuchi
-use crate::parser::is_valid_set;
-use crate::tiles::Tile;
+use crate::parser::{_is_valid, arrange_set, arrangements, set_points, validate_set, InvalidSet};
+use crate::rules::RuleSet;
+use crate::tiles::{_slot, _tile_at, TileMultiset, SLOTS};
+use crate::tiles::{BasicTile, InvalidTileValue, Joker, JokerVariant, Penalties, Tile};
+use crate::tiles::{TileValue, TooManyCopies};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn can_win(board: &Vec<Vec<Tile>>, _rack: &Vec<Tile>) -> Result<(), ()> {
-    for set in board {
-        if !is_valid_set(set) {
-            panic!("Initial board contains an invalid set: {:?}", set);
+/// Slots of the joker variants in a count of tiles.
+const SINGLE_JOKER: usize = 52;
+const DOUBLE_JOKER: usize = 53;
+const MIRROR_JOKER: usize = 54;
+const COLOR_CHANGE_JOKER: usize = 55;
+
+/// The search for the layout that keeps the most board sets in place may visit `REFINE_FACTOR`
+/// positions for every position visited by the search for the best measure, and at least
+/// `REFINE_MIN` positions, before it settles for the best layout found so far.
+const REFINE_FACTOR: usize = 1;
+const REFINE_MIN: usize = 20_000;
+
+/// What to maximise when looking for the best move.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+    /// Play as many tiles from the rack as possible, breaking ties by points.
+    Tiles,
+    /// Play tiles from the rack worth as many points as possible, breaking ties by the number of
+    /// tiles. Jokers are worth the points they would cost if left on the rack, as given by the
+    /// penalties of the rules.
+    Points,
+}
+
+/// A move found by the solver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Play {
+    /// `board` is the board after the move, rearranged into valid sets.
+    pub board: Vec<Vec<Tile>>,
+
+    /// `rack` holds the tiles left on the rack after the move.
+    pub rack: Vec<Tile>,
+
+    /// `disruption` measures how much of the board the move rearranges.
+    pub disruption: Disruption,
+
+    /// `proven` is false when the search stopped before it was done, in which case the move is the
+    /// best one found so far and a better one may exist.
+    pub proven: bool,
+}
+
+/// Limits on how long the solver may search. By default, the search runs until it is done.
+#[derive(Debug, Clone, Default)]
+pub struct SolveConfig {
+    /// `time_limit` is the longest the search may run for.
+    pub time_limit: Option<Duration>,
+
+    /// `node_limit` is the largest number of positions the search may visit.
+    pub node_limit: Option<u64>,
+
+    /// `cancel` stops the search as soon as it is cancelled, from any thread.
+    pub cancel: Option<Cancel>,
+
+    /// `threads` is the number of threads to search on, the calling thread alone if at most one.
+    /// Unless the search stops early, the move found is the same whatever the number of threads.
+    pub threads: usize,
+}
+
+/// A handle to stop a running search from another thread. Clones share the same handle.
+#[derive(Debug, Clone, Default)]
+pub struct Cancel {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop every search given this handle.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Return whether the handle was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// How much of the board a move rearranges. Among moves that play equally well, the solver always
+/// returns one that disturbs the fewest board sets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Disruption {
+    /// `sets` is the number of board sets that were disturbed. A board set stays in place if it lies
+    /// whole within a set after the move, even one extended with more tiles, but when board sets
+    /// are joined together, all but one of them are disturbed.
+    pub sets: usize,
+
+    /// `tiles` is the number of board tiles that were moved. Each set after the move keeps the
+    /// tiles of the board set it shares the most tiles with, as long as it shares at least two.
+    /// Ex. splitting 4 5 6 7 8 9 into 4 5 6 and 7 8 9 moves no tiles
+    ///     joining 1 2 3 and 4 5 6 into 1 2 3 4 5 6 moves 3 tiles
+    ///     taking the 7 from 4 5 6 7 into a group moves 1 tile
+    pub tiles: usize,
+}
+
+/// The reason the solver could not search a position.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveError {
+    /// The set at index `set` on the board is invalid.
+    InvalidBoardSet { set: usize, error: InvalidSet },
+    /// A tile on the rack has a value outside of 1 to 13.
+    InvalidRackTile(InvalidTileValue),
+    /// The board and rack together use more copies of a tile than the box contains.
+    TooManyCopies(TooManyCopies),
+    /// The search ran out of time before finding an answer.
+    Timeout,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveError::InvalidBoardSet { set, error } => {
+                write!(f, "set {} on the board is invalid: {}", set + 1, error)
+            }
+            SolveError::InvalidRackTile(e) => write!(f, "invalid tile on the rack: {}", e),
+            SolveError::TooManyCopies(e) => write!(f, "{}", e),
+            SolveError::Timeout => write!(f, "the search ran out of time"),
         }
     }
-    Err(())
+}
+
+impl Error for SolveError {}
+
+/// Given a board of valid sets and a rack of tiles, return a rearrangement of the board into valid
+/// sets that plays every tile in the rack, or None if the rack cannot be emptied this turn.
+///
+/// Sets are laid out as by `arrange_set`: runs in ascending order and groups in color order. Of
+/// all the winning layouts, the one with the least disruption to the board is returned.
+pub fn can_win(
+    board: &[Vec<Tile>],
+    rack: &[Tile],
+    rules: &RuleSet,
+) -> Result<Option<Vec<Vec<Tile>>>, SolveError> {
+    can_win_with(board, rack, rules, &SolveConfig::default())
+}
+
+/// Same as `can_win`, but gives up with `SolveError::Timeout` once the search goes past the limits
+/// of the configuration without having found a winning layout. A winning layout found before the
+/// search stopped is returned.
+pub fn can_win_with(
+    board: &[Vec<Tile>],
+    rack: &[Tile],
+    rules: &RuleSet,
+    config: &SolveConfig,
+) -> Result<Option<Vec<Vec<Tile>>>, SolveError> {
+    _check_position(board, rack, rules)?;
+    let need = _count(board.iter().flatten().chain(rack));
+    let spare = [0u8; SLOTS];
+    let mut search = Search::new(board, rules, Objective::Tiles, config);
+    let top = search.solve(&need, &spare);
+    match (top, search.found.take()) {
+        (Ok(None), _) => Ok(None),
+        (Ok(Some(_)), _) => Ok(Some(search.layout(need, spare).0)),
+        (Err(_), Some((_, steps))) => Ok(Some(search.resume(need, spare, steps).0)),
+        (Err(error), None) => Err(error),
+    }
+}
+
+/// Given a board of valid sets and a rack of tiles, return the move that plays the most tiles or
+/// points from the rack, depending on the objective, along with the tiles left on the rack.
+///
+/// A move that empties the rack is always preferred, and the board can always be laid out again
+/// without playing anything, so there is always a move to return.
+pub fn best_play(
+    board: &[Vec<Tile>],
+    rack: &[Tile],
+    rules: &RuleSet,
+    objective: Objective,
+) -> Result<Play, SolveError> {
+    best_play_with(board, rack, rules, objective, &SolveConfig::default())
+}
+
+/// Same as `best_play`, but stops once the search goes past the limits of the configuration and
+/// returns the best move found so far, which is not `proven` to be the best. If the search stopped
+/// before finding any layout, the board is returned as it was and the rack is left untouched.
+pub fn best_play_with(
+    board: &[Vec<Tile>],
+    rack: &[Tile],
+    rules: &RuleSet,
+    objective: Objective,
+    config: &SolveConfig,
+) -> Result<Play, SolveError> {
+    _check_position(board, rack, rules)?;
+    let need = _count(board.iter().flatten());
+    let spare = _count(rack);
+    let mut search = Search::new(board, rules, objective, config);
+    let proven = search.solve(&need, &spare).is_ok();
+    let (after, rack) = match (proven, search.found.take()) {
+        (true, _) => search.layout(need, spare),
+        (false, Some((_, steps))) => search.resume(need, spare, steps),
+        (false, None) => (board.to_vec(), rack.to_vec()),
+    };
+    Ok(Play {
+        disruption: disruption(board, &after),
+        board: after,
+        rack,
+        proven,
+    })
+}
+
+/// Given a board of valid sets and the rack of a player who has not yet come down, return the move
+/// that lays down new sets made only from rack tiles, worth at least `rules.initial_meld` points
+/// together, and plays the most tiles, breaking ties by points. Return None if no such move exists.
+///
+/// The board is left untouched and the new sets are laid out after it. Jokers are worth the tiles
+/// they stand for, as counted by `set_points`.
+pub fn initial_meld(
+    board: &[Vec<Tile>],
+    rack: &[Tile],
+    rules: &RuleSet,
+) -> Result<Option<Play>, SolveError> {
+    _check_position(board, rack, rules)?;
+    let spare = _count(rack);
+    let mut search = Meld::new(rules);
+    if search.value(&spare, rules.initial_meld).is_none() {
+        return Ok(None);
+    }
+    let (sets, rack) = search.layout(spare, rules.initial_meld);
+    Ok(Some(Play {
+        board: board.iter().cloned().chain(sets).collect(),
+        rack,
+        disruption: Disruption::default(),
+        proven: true,
+    }))
+}
+
+/// Given a board of valid sets and a rack of tiles, return every distinct rearrangement of the
+/// board into valid sets that plays every tile in the rack, stopping after `limit` of them.
+///
+/// Two boards are the same if they hold the same collections of tiles in some order, so a set is
+/// only laid out one way, as by `can_win`, even when its jokers could stand for other tiles.
+pub fn solutions(
+    board: &[Vec<Tile>],
+    rack: &[Tile],
+    rules: &RuleSet,
+    limit: usize,
+) -> Result<Vec<Vec<Vec<Tile>>>, SolveError> {
+    _check_position(board, rack, rules)?;
+    let tiles = _count(board.iter().flatten().chain(rack));
+    let mut search = Solutions::new(rules, limit);
+    let mut found = Vec::new();
+    search.collect(&tiles, &[0u8; SLOTS], &mut Vec::new(), &mut found);
+    Ok(found)
+}
+
+/// Return the number of distinct boards that `solutions` would return, counting no further than
+/// `limit`.
+pub fn count_solutions(
+    board: &[Vec<Tile>],
+    rack: &[Tile],
+    rules: &RuleSet,
+    limit: usize,
+) -> Result<usize, SolveError> {
+    _check_position(board, rack, rules)?;
+    let tiles = _count(board.iter().flatten().chain(rack));
+    Ok(Solutions::new(rules, limit).count(&tiles, &[0u8; SLOTS]))
+}
+
+/// Given the board before and after a move, return how much of the board the move rearranged.
+/// Basic tiles with an invalid value are never counted as kept in place.
+pub fn disruption(before: &[Vec<Tile>], after: &[Vec<Tile>]) -> Disruption {
+    let board = _board(before);
+    let (mut sets, mut tiles) = (0, 0);
+    for set in after {
+        let counts = _count(set);
+        sets += _kept_sets(&board, &counts);
+        tiles += _kept_tiles(&board, &counts);
+    }
+    Disruption {
+        sets: before.len().saturating_sub(sets as usize),
+        tiles: before
+            .iter()
+            .map(Vec::len)
+            .sum::<usize>()
+            .saturating_sub(tiles as usize),
+    }
+}
+
+/// Check that every set on the board is valid, and that the board and rack only hold real tiles
+/// available in the box.
+fn _check_position(board: &[Vec<Tile>], rack: &[Tile], rules: &RuleSet) -> Result<(), SolveError> {
+    for (index, set) in board.iter().enumerate() {
+        if let Err(error) = validate_set(set, rules) {
+            return Err(SolveError::InvalidBoardSet { set: index, error });
+        }
+    }
+    for tile in rack {
+        if let Tile::Basic(t) = tile {
+            if let Err(e) = BasicTile::new(t.color, t.value) {
+                return Err(SolveError::InvalidRackTile(e));
+            }
+        }
+    }
+    rules
+        .bag
+        .check_position(board, rack)
+        .map_err(SolveError::TooManyCopies)
+}
+
+/// Return the number of tiles in every slot, leaving out basic tiles with an invalid value. Only
+/// `disruption` counts tiles that were not checked first.
+fn _count<'a, I>(tiles: I) -> [u8; SLOTS]
+where
+    I: IntoIterator<Item = &'a Tile>,
+{
+    let mut multiset = TileMultiset::new();
+    for tile in tiles {
+        let _ = multiset.insert(tile);
+    }
+    *multiset.counts()
+}
+
+/// The measure of a move: the two measures of the objective, packed so that comparing measures
+/// compares the first one and then the second one. The second measure takes the low
+/// `MEASURE_BITS` bits. At most 255 copies of each of the 56 kinds of tile, worth at most
+/// `u32::MAX` points each, are worth less than 2^46 points, so neither measure ever needs more.
+type Measure = u128;
+const MEASURE_BITS: u32 = 48;
+
+/// The score of a move: its measure, then the number of board sets it keeps in place, packed in the
+/// same way with the sets in the low `SETS_BITS` bits. A measure takes less than 94 bits, so it
+/// still fits above them. No part of a measure or score ever overflows into the next one, so they
+/// can be added and subtracted as plain numbers.
+type Score = u128;
+const SETS_BITS: u32 = 32;
+
+/// Return the score of a move with the given measure that keeps the given number of sets in place.
+fn _score(measure: Measure, sets: u32) -> Score {
+    debug_assert!(measure >> (Score::BITS - SETS_BITS) == 0);
+    measure << SETS_BITS | sets as Score
+}
+
+/// Return the measure of a score.
+fn _measure(score: Score) -> Measure {
+    score >> SETS_BITS
+}
+
+/// Return the distinct sets of the board, counted per slot.
+fn _board(board: &[Vec<Tile>]) -> Vec<[u8; SLOTS]> {
+    let mut distinct: Vec<[u8; SLOTS]> = Vec::new();
+    for counts in board.iter().map(_count) {
+        if !distinct.contains(&counts) {
+            distinct.push(counts);
+        }
+    }
+    distinct
+}
+
+/// Return whether every tile of the first counts is also in the second.
+fn _within(tiles: &[u8; SLOTS], counts: &[u8; SLOTS]) -> bool {
+    (0..SLOTS).all(|slot| tiles[slot] <= counts[slot])
+}
+
+/// Return 1 if a board set lies whole within the given set, or 0 otherwise. A set can only keep a
+/// single board set in place, as joining sets rearranges all but one of them.
+fn _kept_sets(board: &[[u8; SLOTS]], set: &[u8; SLOTS]) -> u32 {
+    board.iter().any(|old| _within(old, set)) as u32
+}
+
+/// Return the largest number of tiles the given set shares with a single board set, or 0 if it
+/// shares no more than a single tile with any of them.
+fn _kept_tiles(board: &[[u8; SLOTS]], set: &[u8; SLOTS]) -> u32 {
+    board
+        .iter()
+        .map(|old| (0..SLOTS).map(|slot| old[slot].min(set[slot]) as u32).sum())
+        .filter(|&shared| shared >= 2)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Depth-first search for the best layout of a collection of tiles into valid sets.
+///
+/// Implementation:
+/// Tiles are kept as counts per slot, split between the tiles that must be placed (those on the
+/// board) and the tiles that may be placed (those on the rack). Whatever the layout, the lowest
+/// remaining tile either belongs to one of its sets or stays on the rack, so at every step only the
+/// candidate sets containing that tile are tried. Copies of a tile are interchangeable, so a set
+/// always takes the copies that must be placed first. The best measure reachable from every pair
+/// of counts is remembered, so no position is ever searched twice.
+///
+/// The number of board sets kept in place is scored set by set, by comparing every set placed with
+/// the sets of the board before the move. Few layouts keep the whole board in place, and proving
+/// that none does is costly, so this second search is bounded: once the best measure is known,
+/// every position is searched for a score of at least a given floor, raised as better layouts are
+/// found, and is cut off as soon as it cannot reach the floor. It also stops after visiting a
+/// number of positions in proportion to the first search, so that it never costs much more.
+///
+/// The layout itself is rebuilt afterwards by following the remembered scores, or the remembered
+/// measures wherever the second search did not reach.
+///
+/// Both searches stop once they go past the limits of the configuration. A position is only
+/// remembered once it has been searched in full, and the first search keeps track of the best
+/// complete layout found so far, to be returned when it stops early.
+///
+/// On several threads, the positions following each step from the first position are handed out in
+/// order to workers, each remembering what it searched, until one of them reaches the bound. What
+/// any of them remembers is then merged, and the search goes on as on a single thread. The
+/// positions searched from a position do not depend on what was searched before, so the positions
+/// remembered are the same whatever the number of threads, as long as a worker forgets the
+/// positions it searched past the one that reached the bound.
+struct Search<'a> {
+    rules: &'a RuleSet,
+    objective: Objective,
+    config: &'a SolveConfig,
+    table: Arc<SetTable>,
+
+    /// `board` counts the tiles of every distinct board set before the move.
+    board: Vec<[u8; SLOTS]>,
+
+    measures: HashMap<([u8; SLOTS], [u8; SLOTS]), Option<Measure>>,
+    reach: HashMap<([u8; SLOTS], [u8; SLOTS]), Reach>,
+    best: HashMap<([u8; SLOTS], [u8; SLOTS]), Best>,
+
+    /// `budget` is the number of positions the second search may still visit.
+    budget: usize,
+
+    /// `started` is when the search started, and `visited` counts the positions it visited, on
+    /// every thread. `stopped` is set once it goes past the limits of the configuration.
+    started: Instant,
+    visited: Arc<AtomicU64>,
+    stopped: bool,
+
+    /// `path` holds the steps from the first position to the one being searched, and `gained` the
+    /// measure they play.
+    path: Vec<Step>,
+    gained: Measure,
+
+    /// `found` is the best complete layout found so far by the first search: its measure, and the
+    /// steps leading to a position whose remembered measure completes it.
+    found: Option<(Measure, Vec<Step>)>,
+
+    /// `inserted` holds the positions a worker remembered, in order.
+    inserted: Option<Vec<([u8; SLOTS], [u8; SLOTS])>>,
+}
+
+/// What a worker found from one of the positions following the first.
+struct Part {
+    /// `index` is the step leading to the position.
+    index: usize,
+    /// `inserted` is the number of positions the worker remembered before this one.
+    inserted: usize,
+    /// `found` is the best complete layout found from the position, as in `Search`.
+    found: Option<(Measure, Vec<Step>)>,
+}
+
+/// What is known about the best measure of a position.
+#[derive(Clone, Copy)]
+struct Reach {
+    /// `at_least` is a measure that some layout reaches.
+    at_least: Option<Measure>,
+    /// `below` is a measure that no layout reaches.
+    below: Option<Measure>,
+}
+
+/// What is known about the best score of a position.
+#[derive(Clone, Copy)]
+enum Best {
+    /// The best score, or None if the needed tiles cannot all be placed.
+    Exact(Option<Score>),
+    /// Every score is below the floor.
+    Below(Score),
+}
+
+impl<'a> Search<'a> {
+    fn new(
+        board: &[Vec<Tile>],
+        rules: &'a RuleSet,
+        objective: Objective,
+        config: &'a SolveConfig,
+    ) -> Self {
+        Self {
+            rules,
+            objective,
+            config,
+            table: SetTable::get(rules),
+            board: _board(board),
+            measures: HashMap::new(),
+            reach: HashMap::new(),
+            best: HashMap::new(),
+            budget: 0,
+            started: Instant::now(),
+            visited: Arc::new(AtomicU64::new(0)),
+            stopped: false,
+            path: Vec::new(),
+            gained: 0,
+            found: None,
+            inserted: None,
+        }
+    }
+
+    /// Return the best measure for the tiles, or None if the needed tiles cannot all be placed, and
+    /// look for the layout with that measure that keeps the most board sets in place. Return
+    /// `SolveError::Timeout` if the search stopped before the best measure was known.
+    fn solve(
+        &mut self,
+        need: &[u8; SLOTS],
+        spare: &[u8; SLOTS],
+    ) -> Result<Option<Measure>, SolveError> {
+        if self.config.threads > 1 {
+            self.split(need, spare);
+        }
+        let top = self.measure(need, spare);
+        if self.stopped {
+            return Err(SolveError::Timeout);
+        }
+        if let Some(top) = top {
+            self.budget = (self.measures.len() * REFINE_FACTOR).max(REFINE_MIN);
+            self.value(need, spare, _score(top, 0), top);
+        }
+        Ok(top)
+    }
+
+    /// Count a visit to a position, and return whether the search has gone past the limits of the
+    /// configuration.
+    fn expired(&mut self) -> bool {
+        if !self.stopped {
+            let visited = self.visited.fetch_add(1, Ordering::Relaxed) + 1;
+            let config = self.config;
+            self.stopped = config.node_limit.is_some_and(|limit| visited > limit)
+                || config
+                    .time_limit
+                    .is_some_and(|limit| self.started.elapsed() >= limit)
+                || config.cancel.as_ref().is_some_and(Cancel::is_cancelled);
+        }
+        self.stopped
+    }
+
+    /// Search the positions following each step from the first position on worker threads, and
+    /// remember what they found.
+    fn split(&mut self, need: &[u8; SLOTS], spare: &[u8; SLOTS]) {
+        let available = _sum(need, spare);
+        let lowest = match _lowest(&available) {
+            Some(slot) => slot,
+            None => return,
+        };
+        if !_all_playable(need, &available, self.rules) {
+            return;
+        }
+        let bound = self.playable(spare, &available);
+        let steps = self.steps(need, &available, lowest);
+        if steps.is_empty() {
+            return;
+        }
+        let next = AtomicUsize::new(0);
+        let last = AtomicUsize::new(steps.len() - 1);
+        let this = &*self;
+        let workers = thread::scope(|scope| {
+            let handles: Vec<_> = (0..this.config.threads.min(steps.len()))
+                .map(|_| scope.spawn(|| this.work(need, spare, &steps, bound, &next, &last)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        let last = last.into_inner();
+        let mut searched = vec![false; last + 1];
+        for (mut search, parts) in workers {
+            let count = parts.len();
+            for (position, part) in parts.into_iter().enumerate() {
+                if part.index > last {
+                    // A single thread stops before these positions, so forget what was found there.
+                    if let Some(inserted) = &mut search.inserted {
+                        for key in inserted.drain(part.inserted..) {
+                            search.measures.remove(&key);
+                        }
+                    }
+                    break;
+                }
+                if search.stopped && position + 1 == count {
+                    self.stopped = true;
+                } else {
+                    searched[part.index] = true;
+                }
+                if let Some((measure, path)) = part.found {
+                    let step = &steps[part.index];
+                    let (_, _, played) = _take(need, spare, step);
+                    self.path = vec![step.clone()];
+                    self.path.extend(path);
+                    self.keep(self.score(&played) + measure);
+                }
+            }
+            self.measures.extend(search.measures);
+        }
+        self.path.clear();
+        if searched.contains(&false) {
+            self.stopped = true;
+        }
+    }
+
+    /// Search the positions following the steps handed out in order, until one of them reaches the
+    /// bound or the search goes past its limits. Return the worker, with what it remembers, and
+    /// what it found from each position.
+    fn work(
+        &self,
+        need: &[u8; SLOTS],
+        spare: &[u8; SLOTS],
+        steps: &[Step],
+        bound: Measure,
+        next: &AtomicUsize,
+        last: &AtomicUsize,
+    ) -> (Self, Vec<Part>) {
+        let mut search = self.worker();
+        let mut parts = Vec::new();
+        while !search.stopped {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            if index > last.load(Ordering::Relaxed) {
+                break;
+            }
+            let (need, spare, played) = _take(need, spare, &steps[index]);
+            let inserted = search.inserted.as_ref().map_or(0, Vec::len);
+            let rest = search.measure(&need, &spare);
+            if !search.stopped && rest.map(|rest| self.score(&played) + rest) == Some(bound) {
+                last.fetch_min(index, Ordering::Relaxed);
+            }
+            parts.push(Part {
+                index,
+                inserted,
+                found: search.found.take(),
+            });
+        }
+        (search, parts)
+    }
+
+    /// Return a new search sharing the limits of this one, to search from other positions.
+    fn worker(&self) -> Self {
+        Self {
+            rules: self.rules,
+            objective: self.objective,
+            config: self.config,
+            table: Arc::clone(&self.table),
+            board: self.board.clone(),
+            measures: HashMap::new(),
+            reach: HashMap::new(),
+            best: HashMap::new(),
+            budget: 0,
+            started: self.started,
+            visited: Arc::clone(&self.visited),
+            stopped: false,
+            path: Vec::new(),
+            gained: 0,
+            found: None,
+            inserted: Some(Vec::new()),
+        }
+    }
+
+    /// Count a visit to a position by the second search, and return whether it may go on.
+    fn spend(&mut self) -> bool {
+        if self.budget == 0 || self.expired() {
+            return false;
+        }
+        self.budget -= 1;
+        true
+    }
+
+    /// Return the best measure for playing the spare tiles while placing every needed tile, or None
+    /// if the needed tiles cannot all be placed.
+    fn measure(&mut self, need: &[u8; SLOTS], spare: &[u8; SLOTS]) -> Option<Measure> {
+        let available = _sum(need, spare);
+        let lowest = match _lowest(&available) {
+            Some(slot) => slot,
+            None => return Some(0),
+        };
+        if let Some(&measure) = self.measures.get(&(*need, *spare)) {
+            return measure;
+        }
+        if self.expired() {
+            return None;
+        }
+        let mut best = None;
+        if _all_playable(need, &available, self.rules) {
+            let bound = self.playable(spare, &available);
+            for step in self.steps(need, &available, lowest) {
+                let (need, spare, played) = _take(need, spare, &step);
+                let gain = self.score(&played);
+                self.path.push(step);
+                self.gained += gain;
+                let rest = self.measure(&need, &spare);
+                if self.stopped {
+                    return None;
+                }
+                if let Some(rest) = rest {
+                    let total = gain + rest;
+                    self.keep(self.gained + rest);
+                    if Some(total) > best {
+                        best = Some(total);
+                    }
+                    if total == bound {
+                        self.gained -= gain;
+                        self.path.pop();
+                        break;
+                    }
+                }
+                self.gained -= gain;
+                self.path.pop();
+            }
+        }
+        self.measures.insert((*need, *spare), best);
+        if let Some(inserted) = &mut self.inserted {
+            inserted.push((*need, *spare));
+        }
+        best
+    }
+
+    /// Keep the current path as the best complete layout found so far if its measure, completed by
+    /// the remembered measure of the position it leads to, beats the one found before.
+    fn keep(&mut self, measure: Measure) {
+        if self
+            .found
+            .as_ref()
+            .is_none_or(|(found, _)| measure > *found)
+        {
+            self.found = Some((measure, self.path.clone()));
+        }
+    }
+
+    /// Return whether the tiles can be laid out with at least the given measure. Unlike `measure`,
+    /// this stops at the first layout found.
+    fn reaches(&mut self, need: &[u8; SLOTS], spare: &[u8; SLOTS], target: Measure) -> bool {
+        let available = _sum(need, spare);
+        let lowest = match _lowest(&available) {
+            Some(slot) => slot,
+            None => return target == 0,
+        };
+        if let Some(&measure) = self.measures.get(&(*need, *spare)) {
+            return measure >= Some(target);
+        }
+        let known = self.reach.get(&(*need, *spare)).copied();
+        let mut reach = known.unwrap_or(Reach {
+            at_least: None,
+            below: None,
+        });
+        if reach.at_least >= Some(target) {
+            return true;
+        }
+        if matches!(reach.below, Some(measure) if measure <= target) {
+            return false;
+        }
+        if !self.spend() {
+            return false;
+        }
+        let mut reached = false;
+        if _all_playable(need, &available, self.rules) && self.playable(spare, &available) >= target
+        {
+            for step in self.steps(need, &available, lowest) {
+                let (need, spare, played) = _take(need, spare, &step);
+                let rest = target.saturating_sub(self.score(&played));
+                if self.reaches(&need, &spare, rest) {
+                    reached = true;
+                    break;
+                }
+            }
+        }
+        if reached {
+            reach.at_least = Some(target);
+        } else {
+            reach.below = Some(target);
+        }
+        self.reach.insert((*need, *spare), reach);
+        reached
+    }
+
+    /// Return the best score for playing the spare tiles while placing every needed tile, or None
+    /// if the needed tiles cannot all be placed or the best score is below the floor. No layout
+    /// can have a measure above `top`, so the floor is always raised to that measure.
+    fn value(
+        &mut self,
+        need: &[u8; SLOTS],
+        spare: &[u8; SLOTS],
+        floor: Score,
+        top: Measure,
+    ) -> Option<Score> {
+        let available = _sum(need, spare);
+        let lowest = match _lowest(&available) {
+            Some(slot) => slot,
+            None => return Some(0).filter(|&score| score >= floor),
+        };
+        match self.best.get(&(*need, *spare)) {
+            Some(&Best::Exact(score)) => return score.filter(|&score| score >= floor),
+            Some(&Best::Below(missed)) if floor >= missed => return None,
+            _ => {}
+        }
+        let bound = self.bound(&available, top);
+        if bound < floor {
+            return None;
+        }
+        if !self.spend() {
+            return None;
+        }
+        let floor = floor.max(_score(top, 0));
+        let mut best: Option<Score> = None;
+        if self.reaches(need, spare, top) {
+            let mut steps = self.steps(need, &available, lowest);
+            steps.sort_by_cached_key(|step| std::cmp::Reverse(self.kept(step)));
+            for step in steps {
+                let (next_need, next_spare, played) = _take(need, spare, &step);
+                let gain = self.gain(&step, &played);
+                if _measure(gain) > top {
+                    continue;
+                }
+                let wanted = best.map_or(floor, |best| floor.max(best + 1));
+                let rest = wanted.saturating_sub(gain);
+                if let Some(rest) = self.value(&next_need, &next_spare, rest, top - _measure(gain))
+                {
+                    best = Some(gain + rest);
+                    if gain + rest >= bound {
+                        break;
+                    }
+                }
+            }
+        }
+        let memo = match best {
+            Some(_) => Best::Exact(best),
+            None => Best::Below(floor),
+        };
+        self.best.insert((*need, *spare), memo);
+        best
+    }
+
+    /// Rebuild the sets of the best layout found by `solve`, along with the spare tiles left over.
+    fn layout(
+        &mut self,
+        mut need: [u8; SLOTS],
+        mut spare: [u8; SLOTS],
+    ) -> (Vec<Vec<Tile>>, Vec<Tile>) {
+        let mut sets = Vec::new();
+        let mut left = Vec::new();
+        while let Some(lowest) = _lowest(&_sum(&need, &spare)) {
+            let target = self.known(&need, &spare);
+            let measure = self.measured(&need, &spare);
+            let available = _sum(&need, &spare);
+            let mut steps = self.steps(&need, &available, lowest);
+            steps.sort_by_cached_key(|step| std::cmp::Reverse(self.kept(step)));
+            for step in steps {
+                let (next_need, next_spare, played) = _take(&need, &spare, &step);
+                let found = match target {
+                    Some(target) => {
+                        let gain = self.gain(&step, &played);
+                        gain <= target && self.known(&next_need, &next_spare) == Some(target - gain)
+                    }
+                    None => {
+                        let rest = self.measured(&next_need, &next_spare);
+                        rest.map(|rest| self.score(&played) + rest) == measure
+                    }
+                };
+                if found {
+                    match step {
+                        Step::Play(set) => sets.push(set),
+                        Step::Keep(slot) => left.push(_tile_at(slot)),
+                    }
+                    need = next_need;
+                    spare = next_spare;
+                    break;
+                }
+            }
+        }
+        (sets, left)
+    }
+
+    /// Rebuild the sets of the best layout found by the first search before it stopped, given the
+    /// steps leading to a position whose remembered measure completes it.
+    fn resume(
+        &mut self,
+        mut need: [u8; SLOTS],
+        mut spare: [u8; SLOTS],
+        steps: Vec<Step>,
+    ) -> (Vec<Vec<Tile>>, Vec<Tile>) {
+        let mut sets = Vec::new();
+        let mut left = Vec::new();
+        for step in steps {
+            let (next_need, next_spare, _) = _take(&need, &spare, &step);
+            match step {
+                Step::Play(set) => sets.push(set),
+                Step::Keep(slot) => left.push(_tile_at(slot)),
+            }
+            need = next_need;
+            spare = next_spare;
+        }
+        let (rest, kept) = self.layout(need, spare);
+        sets.extend(rest);
+        left.extend(kept);
+        (sets, left)
+    }
+
+    /// Return the best score found by the second search for the position, if it found one.
+    fn known(&self, need: &[u8; SLOTS], spare: &[u8; SLOTS]) -> Option<Score> {
+        if _lowest(&_sum(need, spare)).is_none() {
+            return Some(0);
+        }
+        match self.best.get(&(*need, *spare)) {
+            Some(&Best::Exact(score)) => score,
+            _ => None,
+        }
+    }
+
+    /// Return the best measure found by the first search for the position, if it searched it.
+    fn measured(&self, need: &[u8; SLOTS], spare: &[u8; SLOTS]) -> Option<Measure> {
+        if _lowest(&_sum(need, spare)).is_none() {
+            return Some(0);
+        }
+        self.measures.get(&(*need, *spare)).copied().flatten()
+    }
+
+    /// Return the ways to deal with the lowest remaining tile: every set containing it, and keeping
+    /// it on the rack when it is a spare tile.
+    fn steps(&self, need: &[u8; SLOTS], available: &[u8; SLOTS], lowest: usize) -> Vec<Step> {
+        let mut steps: Vec<Step> = self
+            .table
+            .candidates(available, lowest)
+            .into_iter()
+            .map(Step::Play)
+            .collect();
+        if need[lowest] == 0 {
+            steps.push(Step::Keep(lowest));
+        }
+        steps
+    }
+
+    /// Return how much of the board the step keeps in place, to try the steps that keep the most
+    /// first, as they are the most likely to reach the bound.
+    fn kept(&self, step: &Step) -> (u32, u32) {
+        match step {
+            Step::Play(set) => {
+                let counts = _count(set);
+                (
+                    _kept_sets(&self.board, &counts),
+                    _kept_tiles(&self.board, &counts),
+                )
+            }
+            Step::Keep(_) => (0, 0),
+        }
+    }
+
+    /// Return the score gained by the step, given the spare tiles it plays.
+    fn gain(&self, step: &Step, played: &[u8; SLOTS]) -> Score {
+        let sets = match step {
+            Step::Play(set) => _kept_sets(&self.board, &_count(set)),
+            Step::Keep(_) => 0,
+        };
+        _score(self.score(played), sets)
+    }
+
+    /// Return a score that no layout of the available tiles can beat, given a measure that none
+    /// can beat: every board set kept in place as many times as its tiles allow.
+    fn bound(&self, available: &[u8; SLOTS], top: Measure) -> Score {
+        let sets = self
+            .board
+            .iter()
+            .map(|old| {
+                (0..SLOTS)
+                    .filter(|&slot| old[slot] > 0)
+                    .map(|slot| (available[slot] / old[slot]) as u32)
+                    .min()
+                    .unwrap_or(0)
+            })
+            .sum();
+        _score(top, sets)
+    }
+
+    /// Return a measure that no layout of the available tiles can beat: every spare tile that
+    /// could be played in some set, played.
+    fn playable(&self, spare: &[u8; SLOTS], available: &[u8; SLOTS]) -> Measure {
+        let mut playable = *spare;
+        for slot in (0..52).filter(|&slot| spare[slot] > 0) {
+            if !_playable(slot, available, self.rules) {
+                playable[slot] = 0;
+            }
+        }
+        self.score(&playable)
+    }
+
+    /// Return the measure of playing the given spare tiles.
+    fn score(&self, played: &[u8; SLOTS]) -> Measure {
+        let tiles: Measure = played.iter().map(|&n| n as Measure).sum();
+        let points: Measure = (0..SLOTS)
+            .filter(|&slot| played[slot] > 0)
+            .map(|slot| {
+                played[slot] as Measure * self.rules.penalties.tile(&_tile_at(slot)) as Measure
+            })
+            .sum();
+        match self.objective {
+            Objective::Tiles => tiles << MEASURE_BITS | points,
+            Objective::Points => points << MEASURE_BITS | tiles,
+        }
+    }
+}
+
+/// The number of tiles and points of a meld, compared by its first and then its second element.
+type Laid = (u32, u32);
+
+/// Depth-first search for the initial meld playing the most tiles.
+///
+/// Implementation:
+/// As in `Search`, the lowest remaining tile either belongs to one of its sets or stays on the rack,
+/// but every tile is spare. The points still missing to reach the threshold are part of the
+/// position, and the best meld from every position is remembered. Candidate sets keep the layout
+/// worth the most points, since the jokers in a set may stand for different tiles depending on
+/// where they are placed.
+struct Meld<'a> {
+    rules: &'a RuleSet,
+    table: Arc<SetTable>,
+    best: HashMap<([u8; SLOTS], u32), Option<Laid>>,
+    sets: HashMap<[u8; SLOTS], (Vec<Tile>, u32)>,
+}
+
+impl<'a> Meld<'a> {
+    fn new(rules: &'a RuleSet) -> Self {
+        Self {
+            rules,
+            table: SetTable::get(rules),
+            best: HashMap::new(),
+            sets: HashMap::new(),
+        }
+    }
+
+    /// Return the best meld of the spare tiles worth at least the missing points, or None if no
+    /// meld is worth that many.
+    fn value(&mut self, spare: &[u8; SLOTS], missing: u32) -> Option<Laid> {
+        let lowest = match _lowest(spare) {
+            Some(slot) => slot,
+            None => return Some((0, 0)).filter(|_| missing == 0),
+        };
+        if let Some(&laid) = self.best.get(&(*spare, missing)) {
+            return laid;
+        }
+        let mut best = None;
+        for step in self.steps(spare, lowest) {
+            let (tiles, points) = self.laid(&step);
+            let (_, next, _) = _take(&[0u8; SLOTS], spare, &step);
+            if let Some(rest) = self.value(&next, missing.saturating_sub(points)) {
+                let total = (tiles + rest.0, points + rest.1);
+                if Some(total) > best {
+                    best = Some(total);
+                }
+            }
+        }
+        self.best.insert((*spare, missing), best);
+        best
+    }
+
+    /// Rebuild the sets of the best meld found by `value`, along with the spare tiles left over.
+    fn layout(&mut self, mut spare: [u8; SLOTS], mut missing: u32) -> (Vec<Vec<Tile>>, Vec<Tile>) {
+        let mut sets = Vec::new();
+        let mut left = Vec::new();
+        while let Some(lowest) = _lowest(&spare) {
+            let target = self.value(&spare, missing);
+            for step in self.steps(&spare, lowest) {
+                let (tiles, points) = self.laid(&step);
+                let (_, next, _) = _take(&[0u8; SLOTS], &spare, &step);
+                let rest = self.value(&next, missing.saturating_sub(points));
+                if rest.map(|rest| (tiles + rest.0, points + rest.1)) == target {
+                    match step {
+                        Step::Play(set) => sets.push(set),
+                        Step::Keep(slot) => left.push(_tile_at(slot)),
+                    }
+                    spare = next;
+                    missing = missing.saturating_sub(points);
+                    break;
+                }
+            }
+        }
+        (sets, left)
+    }
+
+    /// Return the ways to deal with the lowest remaining tile: every set containing it, laid out
+    /// to be worth the most points, and keeping it on the rack.
+    fn steps(&mut self, spare: &[u8; SLOTS], lowest: usize) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for set in self.table.candidates(spare, lowest) {
+            let set = self.best_layout(set);
+            steps.push(Step::Play(set));
+        }
+        steps.push(Step::Keep(lowest));
+        steps
+    }
+
+    /// Return the layout of the tiles of the set worth the most points, remembering its points.
+    fn best_layout(&mut self, set: Vec<Tile>) -> Vec<Tile> {
+        let key = _count(&set);
+        if let Some((layout, _)) = self.sets.get(&key) {
+            return layout.clone();
+        }
+        let rules = self.rules;
+        let points = |set: &[Tile]| set_points(set, rules).unwrap_or(0);
+        let mut most = points(&set);
+        let mut best = set;
+        if best.iter().any(|tile| matches!(tile, Tile::Joker(_))) {
+            for layout in arrangements(&best, rules) {
+                let points = points(&layout);
+                if points > most {
+                    most = points;
+                    best = layout;
+                }
+            }
+        }
+        self.sets.insert(key, (best.clone(), most));
+        best
+    }
+
+    /// Return the number of tiles and points the step lays down.
+    fn laid(&self, step: &Step) -> Laid {
+        match step {
+            Step::Play(set) => (set.len() as u32, self.sets[&_count(set)].1),
+            Step::Keep(_) => (0, 0),
+        }
+    }
+}
+
+/// Depth-first search for every way to lay out a collection of tiles into valid sets.
+///
+/// Implementation:
+/// As in `Search`, the lowest remaining tile belongs to one of the sets of every layout, so every
+/// layout is reached by taking the set containing it, then laying out the rest. When several sets
+/// contain copies of the lowest tile, they are taken in increasing order of their counts, so that
+/// every layout is reached once. The number of layouts from every position is remembered, up to
+/// the limit, along with the smallest set it may still take.
+struct Solutions<'a> {
+    rules: &'a RuleSet,
+    table: Arc<SetTable>,
+    limit: usize,
+    counts: HashMap<([u8; SLOTS], [u8; SLOTS]), usize>,
+}
+
+impl<'a> Solutions<'a> {
+    fn new(rules: &'a RuleSet, limit: usize) -> Self {
+        Self {
+            rules,
+            table: SetTable::get(rules),
+            limit,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Return the number of layouts of the tiles whose sets containing the lowest tile are no
+    /// smaller than the floor, counting no further than the limit.
+    fn count(&mut self, tiles: &[u8; SLOTS], floor: &[u8; SLOTS]) -> usize {
+        let lowest = match _lowest(tiles) {
+            Some(slot) => slot,
+            None => return 1.min(self.limit),
+        };
+        if let Some(&count) = self.counts.get(&(*tiles, *floor)) {
+            return count;
+        }
+        let mut total = 0;
+        if _all_playable(tiles, tiles, self.rules) {
+            for (rest, floor) in self.steps(tiles, floor, lowest) {
+                total = (total + self.count(&rest, &floor)).min(self.limit);
+                if total == self.limit {
+                    break;
+                }
+            }
+        }
+        self.counts.insert((*tiles, *floor), total);
+        total
+    }
+
+    /// Add the layouts of the tiles to those found, after the sets already taken, until the limit
+    /// is reached.
+    fn collect(
+        &mut self,
+        tiles: &[u8; SLOTS],
+        floor: &[u8; SLOTS],
+        sets: &mut Vec<Vec<Tile>>,
+        found: &mut Vec<Vec<Vec<Tile>>>,
+    ) {
+        let lowest = match _lowest(tiles) {
+            Some(slot) => slot,
+            None => {
+                if found.len() < self.limit {
+                    found.push(sets.clone());
+                }
+                return;
+            }
+        };
+        for set in self.table.candidates(tiles, lowest) {
+            if found.len() == self.limit {
+                return;
+            }
+            let counts = _count(&set);
+            if counts < *floor {
+                continue;
+            }
+            let (rest, floor) = _after(tiles, &counts, lowest);
+            if self.count(&rest, &floor) > 0 {
+                sets.push(set);
+                self.collect(&rest, &floor, sets, found);
+                sets.pop();
+            }
+        }
+    }
+
+    /// Return the tiles left by every set containing the lowest tile and no smaller than the floor,
+    /// along with the floor for the tiles left.
+    fn steps(
+        &self,
+        tiles: &[u8; SLOTS],
+        floor: &[u8; SLOTS],
+        lowest: usize,
+    ) -> Vec<([u8; SLOTS], [u8; SLOTS])> {
+        self.table
+            .candidates(tiles, lowest)
+            .iter()
+            .map(_count)
+            .filter(|counts| counts >= floor)
+            .map(|counts| _after(tiles, &counts, lowest))
+            .collect()
+    }
+}
+
+/// Remove the counted tiles of a set containing the lowest tile, and return the tiles left along
+/// with the smallest set they may still take: the set itself if it left copies of the lowest tile,
+/// any set otherwise.
+fn _after(tiles: &[u8; SLOTS], set: &[u8; SLOTS], lowest: usize) -> ([u8; SLOTS], [u8; SLOTS]) {
+    let mut rest = *tiles;
+    for (count, taken) in rest.iter_mut().zip(set) {
+        *count -= taken;
+    }
+    let floor = if rest[lowest] > 0 { *set } else { [0u8; SLOTS] };
+    (rest, floor)
+}
+
+/// A way to deal with the lowest remaining tile.
+#[derive(Clone)]
+enum Step {
+    /// Play the set.
+    Play(Vec<Tile>),
+    /// Keep the tile in the slot on the rack.
+    Keep(usize),
+}
+
+/// Remove the tiles of the step from the counts, taking the needed copies first, and return the
+/// counts left along with the spare tiles that were played.
+fn _take(
+    need: &[u8; SLOTS],
+    spare: &[u8; SLOTS],
+    step: &Step,
+) -> ([u8; SLOTS], [u8; SLOTS], [u8; SLOTS]) {
+    let (mut need, mut spare) = (*need, *spare);
+    let mut played = [0u8; SLOTS];
+    match step {
+        Step::Play(set) => {
+            for tile in set {
+                let slot = _slot(tile).unwrap();
+                if need[slot] > 0 {
+                    need[slot] -= 1;
+                } else {
+                    spare[slot] -= 1;
+                    played[slot] += 1;
+                }
+            }
+        }
+        Step::Keep(slot) => spare[*slot] -= 1,
+    }
+    (need, spare, played)
+}
+
+fn _sum(a: &[u8; SLOTS], b: &[u8; SLOTS]) -> [u8; SLOTS] {
+    let mut sum = *a;
+    for (slot, count) in sum.iter_mut().enumerate() {
+        *count += b[slot];
+    }
+    sum
+}
+
+/// Return the slot of the remaining tile with the lowest value, taking colors in order and jokers
+/// last, or None if no tiles remain.
+fn _lowest(counts: &[u8; SLOTS]) -> Option<usize> {
+    (0..13)
+        .flat_map(|value| (0..4).map(move |color| color * 13 + value))
+        .chain(SINGLE_JOKER..SLOTS)
+        .find(|&slot| counts[slot] > 0)
+}
+
+/// Return whether every needed basic tile could still be played in a set of the shortest length
+/// made from the available tiles, ignoring that the other tiles of that set might be needed
+/// elsewhere. This is a quick test to abandon branches that left a tile stranded.
+fn _all_playable(need: &[u8; SLOTS], available: &[u8; SLOTS], rules: &RuleSet) -> bool {
+    (0..52)
+        .filter(|&slot| need[slot] > 0)
+        .all(|slot| _playable(slot, available, rules))
+}
+
+/// Return whether the basic tile in the given slot could be played in a set of the shortest length
+/// made from the available tiles.
+fn _playable(slot: usize, available: &[u8; SLOTS], rules: &RuleSet) -> bool {
+    let jokers = _units(available, rules) as usize;
+    let changes = _jokers(available, COLOR_CHANGE_JOKER, rules) as usize;
+    let mirrors = _jokers(available, MIRROR_JOKER, rules) as usize;
+    let min = rules.min_set_size.max(1) as usize;
+    let at = |color: usize, value: usize| available[color * 13 + value % 13] > 0;
+    // Ex. 5 M 5 .. or 5 M J .. is always possible with a mirror joker
+    if mirrors > 0 && (available[slot] > 1 || jokers > 0) {
+        return true;
+    }
+    let (color, value) = (slot / 13, slot % 13);
+    let colors = (0..4).filter(|&c| at(c, value)).count();
+    if colors + jokers >= min && min <= rules.max_group_size as usize {
+        return true;
+    }
+    // No run can be longer than 13 tiles, even when it wraps around.
+    if min > 13 {
+        return false;
+    }
+    let starts = if rules.wrap_around {
+        value + 13 + 1 - min..=value + 13
+    } else {
+        value.saturating_sub(min - 1) + 13..=value.min(13 - min) + 13
+    };
+    // Past a color-change joker, the run may continue in any other color.
+    let fits = |v: usize| at(color, v) || (changes > 0 && (0..4).any(|c| at(c, v)));
+    starts.into_iter().any(|start| {
+        let missing = (start..start + min).filter(|&v| !fits(v)).count();
+        missing <= jokers + changes
+    })
+}
+
+/// Return the number of jokers in the given slot that may be played under the rules.
+fn _jokers(counts: &[u8; SLOTS], slot: usize, rules: &RuleSet) -> u8 {
+    match _tile_at(slot) {
+        Tile::Joker(j) if rules.allows(&j.variant) => counts[slot],
+        _ => 0,
+    }
+}
+
+/// Return the number of positions that single and double jokers can stand in for together.
+fn _units(counts: &[u8; SLOTS], rules: &RuleSet) -> u8 {
+    _jokers(counts, SINGLE_JOKER, rules) + 2 * _jokers(counts, DOUBLE_JOKER, rules)
+}
+
+/// Every valid set made of basic tiles, single jokers and double jokers under a set of rules, by
+/// its lowest tile, built the first time the sets with a given lowest tile are needed.
+///
+/// Implementation:
+/// Mirror and color-change jokers multiply the number of valid sets far beyond what could be
+/// listed: with the box of the special jokers edition, over half a million sets have a red 1 as
+/// their lowest tile. The sets containing them are never listed, and whenever such a joker that
+/// may be played is left, the candidate sets are built by `_candidates` instead of looked up.
+struct SetTable {
+    rules: RuleSet,
+
+    /// `sets` holds the sets whose lowest tile is in each slot.
+    sets: Vec<OnceLock<Listed>>,
+}
+
+/// The sets of a `SetTable` with the same lowest tile, in the order `_candidates` finds them, and
+/// the tree that indexes them by their tiles.
+struct Listed {
+    sets: Vec<Vec<Tile>>,
+    tree: SetTree,
+}
+
+/// A tree indexing sets by the tiles they are made of. Every branch takes some copies of the tile
+/// in a slot above the slots taken on the way to it, so the tiles taken on the way to a node are
+/// the tiles of the set at the node, if any.
+#[derive(Default)]
+struct SetTree {
+    set: Option<usize>,
+    branches: Vec<(usize, u8, SetTree)>,
+}
+
+impl SetTree {
+    /// Add the set with the given index, which takes the given number of copies from each slot in
+    /// ascending order of slots.
+    fn insert(&mut self, taken: &[(usize, u8)], set: usize) {
+        let ((slot, copies), rest) = match taken.split_first() {
+            Some((&first, rest)) => (first, rest),
+            None => {
+                self.set = Some(set);
+                return;
+            }
+        };
+        let branch = match self
+            .branches
+            .iter()
+            .position(|b| (b.0, b.1) == (slot, copies))
+        {
+            Some(branch) => branch,
+            None => {
+                self.branches.push((slot, copies, SetTree::default()));
+                self.branches.len() - 1
+            }
+        };
+        self.branches[branch].2.insert(rest, set);
+    }
+
+    /// Add the indices of every set below the node that can be made from the counted tiles.
+    fn find(&self, counts: &[u8; SLOTS], found: &mut Vec<usize>) {
+        found.extend(self.set);
+        for (slot, copies, tree) in &self.branches {
+            if counts[*slot] >= *copies {
+                tree.find(counts, found);
+            }
+        }
+    }
+}
+
+/// The tables built so far, from the least to the most recently used. At most `TABLES_KEPT` tables
+/// are kept, and the least recently used one is dropped to make room for a new one.
+static TABLES: Mutex<Vec<Arc<SetTable>>> = Mutex::new(Vec::new());
+const TABLES_KEPT: usize = 8;
+
+impl SetTable {
+    /// Return the table for the rules, creating it the first time they are seen. Rules that only
+    /// differ in the points of the tiles share a table, since they allow the same sets.
+    fn get(rules: &RuleSet) -> Arc<SetTable> {
+        let rules = RuleSet {
+            initial_meld: 0,
+            penalties: Penalties::standard(),
+            ..rules.clone()
+        };
+        let mut tables = TABLES.lock().unwrap();
+        if let Some(index) = tables.iter().position(|table| table.rules == rules) {
+            let table = tables.remove(index);
+            tables.push(Arc::clone(&table));
+            return table;
+        }
+        if tables.len() == TABLES_KEPT {
+            tables.remove(0);
+        }
+        let table = Arc::new(SetTable {
+            rules,
+            sets: (0..SLOTS).map(|_| OnceLock::new()).collect(),
+        });
+        tables.push(Arc::clone(&table));
+        table
+    }
+
+    /// Return every valid set that contains the lowest counted tile, in the given slot, and can be
+    /// made from the counted tiles, as `_candidates` does.
+    fn candidates(&self, counts: &[u8; SLOTS], lowest: usize) -> Vec<Vec<Tile>> {
+        let special = [MIRROR_JOKER, COLOR_CHANGE_JOKER];
+        if special
+            .iter()
+            .any(|&joker| _jokers(counts, joker, &self.rules) > 0)
+        {
+            return _candidates(counts, lowest, &self.rules);
+        }
+        let listed = self.lowest(lowest);
+        let mut found = Vec::new();
+        listed.tree.find(counts, &mut found);
+        found.sort_unstable();
+        found
+            .into_iter()
+            .map(|set| listed.sets[set].clone())
+            .collect()
+    }
+
+    /// Return every set of basic tiles, single jokers and double jokers whose lowest tile is in the
+    /// given slot.
+    fn lowest(&self, lowest: usize) -> &Listed {
+        self.sets[lowest].get_or_init(|| {
+            let mut counts = [0u8; SLOTS];
+            for (slot, count) in counts.iter_mut().enumerate().take(DOUBLE_JOKER + 1) {
+                if slot >= lowest {
+                    *count = self.rules.bag.copies(&_tile_at(slot));
+                }
+            }
+            let sets = _candidates(&counts, lowest, &self.rules);
+            let mut tree = SetTree::default();
+            for (index, set) in sets.iter().enumerate() {
+                let tiles = _count(set);
+                let taken: Vec<(usize, u8)> = (0..SLOTS)
+                    .filter(|&slot| tiles[slot] > 0)
+                    .map(|slot| (slot, tiles[slot]))
+                    .collect();
+                tree.insert(&taken, index);
+            }
+            Listed { sets, tree }
+        })
+    }
+}
+
+/// Return every valid set that contains the tile in the given slot and can be made from the
+/// counted tiles, keeping a single layout for every distinct collection of tiles.
+///
+/// Implementation:
+/// Candidates are built in two stages. First, the shapes of every run and group containing the
+/// tile are enumerated position by position: each position holds a basic tile, a joker or a
+/// color-change joker. Shapes are built both for whole sets and for the half of a set on either
+/// side of a mirror joker. Second, every shape is covered with actual tiles, trying single and
+/// double jokers for the joker positions, and every side of the mirror for mirrored shapes.
+/// Every resulting sequence is checked with `_is_valid` before it is kept.
+fn _candidates(counts: &[u8; SLOTS], slot: usize, rules: &RuleSet) -> Vec<Vec<Tile>> {
+    let tile = match _tile_at(slot) {
+        Tile::Basic(tile) => tile,
+        // Only jokers remain, so they can only be played in a set of their own.
+        Tile::Joker(_) => return _joker_sets(counts, slot, rules),
+    };
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    let mut keep = |set: Vec<Tile>| {
+        let key = _count(&set);
+        if !seen.contains(&key) && _is_valid(&set, rules) {
+            seen.insert(key);
+            found.push(set);
+        }
+    };
+    for (shape, _) in _shapes(&tile, counts, rules, 1) {
+        for (set, _) in _cover(&shape, counts, rules) {
+            keep(set);
+        }
+    }
+    if _jokers(counts, MIRROR_JOKER, rules) > 0 {
+        let mut counts = *counts;
+        counts[MIRROR_JOKER] -= 1;
+        for (shape, fixed) in _shapes(&tile, &counts, rules, 2) {
+            for set in _cover_mirrored(&shape, fixed, &counts, rules) {
+                keep(set);
+            }
+        }
+    }
+    found
+}
+
+/// Return every set made only of the counted jokers that contains a joker from the given slot.
+fn _joker_sets(counts: &[u8; SLOTS], slot: usize, rules: &RuleSet) -> Vec<Vec<Tile>> {
+    let mut found = Vec::new();
+    let mut tiles = Vec::new();
+    _joker_sets_from(SINGLE_JOKER, counts, slot, rules, &mut tiles, &mut found);
+    found
+}
+
+fn _joker_sets_from(
+    next: usize,
+    counts: &[u8; SLOTS],
+    slot: usize,
+    rules: &RuleSet,
+    tiles: &mut Vec<Tile>,
+    found: &mut Vec<Vec<Tile>>,
+) {
+    if next == SLOTS {
+        if tiles.len() >= rules.min_set_size as usize && tiles.contains(&_tile_at(slot)) {
+            if let Some(set) = arrange_set(tiles, rules) {
+                found.push(set);
+            }
+        }
+        return;
+    }
+    let len = tiles.len();
+    for copies in 0..=_jokers(counts, next, rules) {
+        tiles.resize(len + copies as usize, _tile_at(next));
+        _joker_sets_from(next + 1, counts, slot, rules, tiles, found);
+    }
+    tiles.truncate(len);
+}
+
+/// A position in the shape of a set.
+#[derive(Clone, Copy, PartialEq)]
+enum Spot {
+    /// The basic tile in the slot. In a mirrored set, the tile must be played on at least one side
+    /// of the mirror.
+    Basic(usize),
+    /// A position held by jokers only.
+    Joker,
+    /// A color-change joker.
+    ColorChange,
+}
+
+/// Return the shapes of every run and group containing the given basic tile that could be made
+/// from the counted tiles, along with the position of the tile in the shape. Runs are laid out in
+/// ascending order and groups in color order, with jokers last.
+///
+/// With two `sides`, the shapes are halves of a mirrored set, and every joker position needs a
+/// joker on both sides of the mirror.
+fn _shapes(
+    tile: &BasicTile,
+    counts: &[u8; SLOTS],
+    rules: &RuleSet,
+    sides: u8,
+) -> Vec<(Vec<Spot>, usize)> {
+    let mut shapes = Shapes {
+        tile: _slot(&Tile::Basic(*tile)).unwrap(),
+        counts,
+        rules,
+        sides,
+        min_len: if sides == 1 {
+            rules.min_set_size as usize
+        } else {
+            1
+        },
+        found: Vec::new(),
+    };
+    for start in 1..=13 {
+        if start <= tile.value || rules.wrap_around {
+            let fixed = (tile.value + 13 - start) as usize % 13;
+            shapes.run(&mut Vec::new(), start, fixed, None, 0, 0);
+        }
+    }
+    shapes.groups();
+    shapes.found
+}
+
+struct Shapes<'a> {
+    tile: usize,
+    counts: &'a [u8; SLOTS],
+    rules: &'a RuleSet,
+    sides: u8,
+    min_len: usize,
+    found: Vec<(Vec<Spot>, usize)>,
+}
+
+impl<'a> Shapes<'a> {
+    /// Extend a run starting at the given value, in which the tile is at position `fixed`. The
+    /// color of the current stretch of the run is unknown until it holds a basic tile.
+    fn run(
+        &mut self,
+        spots: &mut Vec<Spot>,
+        start: TileValue,
+        fixed: usize,
+        stretch: Option<usize>,
+        jokers: u8,
+        changes: u8,
+    ) {
+        let position = spots.len();
+        if position > fixed && position >= self.min_len {
+            self.found.push((spots.clone(), fixed));
+        }
+        // No run can be longer than 13 tiles, even when it wraps around.
+        if position == 13 || (!self.rules.wrap_around && start as usize + position > 13) {
+            return;
+        }
+        let value = (start as usize - 1 + position) % 13;
+
+        if position == fixed {
+            let color = self.tile / 13;
+            if stretch.is_none() || stretch == Some(color) {
+                spots.push(Spot::Basic(self.tile));
+                self.run(spots, start, fixed, Some(color), jokers, changes);
+                spots.pop();
+            }
+            return;
+        }
+        for color in 0..4 {
+            let slot = color * 13 + value;
+            if (stretch.is_none() || stretch == Some(color)) && self.counts[slot] > 0 {
+                spots.push(Spot::Basic(slot));
+                self.run(spots, start, fixed, Some(color), jokers, changes);
+                spots.pop();
+            }
+        }
+        if (jokers + 1) * self.sides <= _units(self.counts, self.rules) {
+            spots.push(Spot::Joker);
+            self.run(spots, start, fixed, stretch, jokers + 1, changes);
+            spots.pop();
+        }
+        let adjacent = spots.last() == Some(&Spot::ColorChange);
+        if (changes + 1) * self.sides <= _jokers(self.counts, COLOR_CHANGE_JOKER, self.rules)
+            && (self.rules.adjacent_color_changes || !adjacent)
+        {
+            spots.push(Spot::ColorChange);
+            self.run(spots, start, fixed, None, jokers, changes + 1);
+            spots.pop();
+        }
+    }
+
+    /// Add every group containing the tile.
+    fn groups(&mut self) {
+        let value = self.tile % 13;
+        let max = (self.rules.max_group_size as usize).min(4);
+        for mask in 0..16 {
+            let slots: Vec<usize> = (0..4)
+                .filter(|&color| mask & (1 << color) != 0)
+                .map(|color| color * 13 + value)
+                .collect();
+            if !slots.contains(&self.tile) || slots.iter().any(|&slot| self.counts[slot] == 0) {
+                continue;
+            }
+            let mut jokers = 0;
+            while slots.len() + jokers <= max
+                && jokers as u8 * self.sides <= _units(self.counts, self.rules)
+            {
+                if slots.len() + jokers >= self.min_len {
+                    self.group(&slots, jokers);
+                }
+                jokers += 1;
+            }
+        }
+    }
+
+    /// Add the group of the given basic tiles and jokers. On either side of a mirror, a double
+    /// joker may face two different tiles, so the jokers are placed in every position. Otherwise,
+    /// they are placed last.
+    fn group(&mut self, slots: &[usize], jokers: usize) {
+        let len = slots.len() + jokers;
+        for mask in 0..(1 << len) {
+            let joker_at = |position: usize| mask & (1 << position) != 0;
+            let placed = (0..len).filter(|&position| joker_at(position)).count();
+            let last = (0..len).all(|position| joker_at(position) == (position >= slots.len()));
+            if placed != jokers || (self.sides == 1 && !last) {
+                continue;
+            }
+            let mut basics = slots.iter();
+            let spots: Vec<Spot> = (0..len)
+                .map(|position| match joker_at(position) {
+                    true => Spot::Joker,
+                    false => Spot::Basic(*basics.next().unwrap()),
+                })
+                .collect();
+            let fixed = spots
+                .iter()
+                .position(|&spot| spot == Spot::Basic(self.tile))
+                .unwrap();
+            self.found.push((spots, fixed));
+        }
+    }
+}
+
+/// Return every way to cover the shape with the counted tiles, along with the counts left over.
+/// Basic positions hold their basic tile, and joker positions hold single jokers or double jokers
+/// spanning two joker positions.
+fn _cover(spots: &[Spot], counts: &[u8; SLOTS], rules: &RuleSet) -> Vec<(Vec<Tile>, [u8; SLOTS])> {
+    let mut found = Vec::new();
+    let mut counts = *counts;
+    _cover_from(spots, &mut counts, rules, &mut Vec::new(), &mut found);
+    found
+}
+
+fn _cover_from(
+    spots: &[Spot],
+    counts: &mut [u8; SLOTS],
+    rules: &RuleSet,
+    tiles: &mut Vec<Tile>,
+    found: &mut Vec<(Vec<Tile>, [u8; SLOTS])>,
+) {
+    let mut place = |slot: usize, rest: &[Spot], counts: &mut [u8; SLOTS]| {
+        counts[slot] -= 1;
+        tiles.push(_tile_at(slot));
+        _cover_from(rest, counts, rules, tiles, found);
+        tiles.pop();
+        counts[slot] += 1;
+    };
+    match spots.first() {
+        None => found.push((tiles.clone(), *counts)),
+        Some(Spot::Basic(slot)) => {
+            if counts[*slot] > 0 {
+                place(*slot, &spots[1..], counts);
+            }
+        }
+        Some(Spot::Joker) => {
+            if _jokers(counts, SINGLE_JOKER, rules) > 0 {
+                place(SINGLE_JOKER, &spots[1..], counts);
+            }
+            if spots.get(1) == Some(&Spot::Joker) && _jokers(counts, DOUBLE_JOKER, rules) > 0 {
+                place(DOUBLE_JOKER, &spots[2..], counts);
+            }
+        }
+        Some(Spot::ColorChange) => {
+            if _jokers(counts, COLOR_CHANGE_JOKER, rules) > 0 {
+                place(COLOR_CHANGE_JOKER, &spots[1..], counts);
+            }
+        }
+    }
+}
+
+/// Return every way to cover both sides of a mirrored set with the given half shape. Every basic
+/// position holds its basic tile on at least one side, with a joker facing it otherwise. The tile
+/// at position `fixed` is always played on the left side, since reversing a mirrored set swaps the
+/// sides.
+fn _cover_mirrored(
+    spots: &[Spot],
+    fixed: usize,
+    counts: &[u8; SLOTS],
+    rules: &RuleSet,
+) -> Vec<Vec<Tile>> {
+    let mut found = Vec::new();
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    _cover_sides(
+        spots, fixed, counts, rules, &mut left, &mut right, &mut found,
+    );
+    found
+}
+
+fn _cover_sides(
+    spots: &[Spot],
+    fixed: usize,
+    counts: &[u8; SLOTS],
+    rules: &RuleSet,
+    left: &mut Vec<Spot>,
+    right: &mut Vec<Spot>,
+    found: &mut Vec<Vec<Tile>>,
+) {
+    let position = left.len();
+    if position == spots.len() {
+        for (left, counts) in _cover(left, counts, rules) {
+            for (right, _) in _cover(right, &counts, rules) {
+                let mut set = left.clone();
+                set.push(Tile::Joker(Joker::new(JokerVariant::Mirror)));
+                set.extend(right.into_iter().rev());
+                found.push(set);
+            }
+        }
+        return;
+    }
+    let jokers = left
+        .iter()
+        .chain(right.iter())
+        .filter(|&&s| s == Spot::Joker)
+        .count();
+    let spare = jokers < _units(counts, rules) as usize;
+    let mut choose = |l: Spot, r: Spot| {
+        left.push(l);
+        right.push(r);
+        _cover_sides(spots, fixed, counts, rules, left, right, found);
+        left.pop();
+        right.pop();
+    };
+    match spots[position] {
+        Spot::Basic(slot) => {
+            if counts[slot] > 1 {
+                choose(Spot::Basic(slot), Spot::Basic(slot));
+            }
+            if spare {
+                choose(Spot::Basic(slot), Spot::Joker);
+                if position != fixed {
+                    choose(Spot::Joker, Spot::Basic(slot));
+                }
+            }
+        }
+        spot => choose(spot, spot),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::InvalidReason;
+    use crate::tiles::{Penalties, TileBag, TileColor};
+
+    /// Check that the solution is made of valid sets using exactly the tiles of the position.
+    fn assert_plays(solution: &[Vec<Tile>], board: &[Vec<Tile>], rack: &[Tile], rules: &RuleSet) {
+        let mut expected = [0u8; SLOTS];
+        for tile in board.iter().flatten().chain(rack) {
+            expected[_slot(tile).unwrap()] += 1;
+        }
+        let mut actual = [0u8; SLOTS];
+        for set in solution {
+            assert!(validate_set(set, rules).is_ok(), "invalid set {:?}", set);
+            for tile in set {
+                actual[_slot(tile).unwrap()] += 1;
+            }
+        }
+        assert_eq!(actual, expected);
+    }
+
+    // WINNING
+
+    #[test]
+    fn test_win_from_empty_board() {
+        let board = vec![];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        let solution = can_win(&board, &rack, &rules).unwrap().unwrap();
+        let expected = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+        ]];
+        assert_eq!(solution, expected);
+    }
+
+    #[test]
+    fn test_win_with_empty_rack() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 4).unwrap()),
+        ]];
+        let rules = RuleSet::standard();
+        let solution = can_win(&board, &[], &rules).unwrap().unwrap();
+        assert_plays(&solution, &board, &[], &rules);
+    }
+
+    #[test]
+    fn test_win_by_splitting_a_run() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+        ]];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        let solution = can_win(&board, &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &board, &rack, &rules);
+        assert_eq!(solution.len(), 2);
+    }
+
+    #[test]
+    fn test_win_by_freeing_a_joker() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ]];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 9).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        let solution = can_win(&board, &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &board, &rack, &rules);
+    }
+
+    #[test]
+    fn test_win_with_jokers_only_set() {
+        let mut rules = RuleSet::standard();
+        rules.bag = TileBag {
+            single_jokers: 3,
+            ..TileBag::standard()
+        };
+        let rack = vec![Tile::Joker(Joker::new(JokerVariant::Single)); 3];
+        let solution = can_win(&[], &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &[], &rack, &rules);
+    }
+
+    #[test]
+    fn test_win_with_wrap_around() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 13).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 12).unwrap()),
+        ];
+        let mut rules = RuleSet::standard();
+        assert_eq!(can_win(&[], &rack, &rules), Ok(None));
+        rules.wrap_around = true;
+        let solution = can_win(&[], &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &[], &rack, &rules);
+    }
+
+    #[test]
+    fn test_win_with_any_min_set_size() {
+        let rack: Vec<Tile> = (1..=13)
+            .map(|value| Tile::Basic(BasicTile::new(TileColor::Red, value).unwrap()))
+            .collect();
+        for &wrap_around in &[false, true] {
+            for &min_set_size in &[0, 13, 14, 255] {
+                let rules = RuleSet {
+                    wrap_around,
+                    min_set_size,
+                    ..RuleSet::standard()
+                };
+                let solution = can_win(&[], &rack, &rules).unwrap();
+                assert_eq!(solution.is_some(), min_set_size <= 13);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cannot_win_with_leftover_tile() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ]];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 9).unwrap()),
+        ];
+        assert_eq!(can_win(&board, &rack, &RuleSet::standard()), Ok(None));
+    }
+
+    #[test]
+    fn test_cannot_win_without_enough_jokers() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+        ];
+        assert_eq!(can_win(&[], &rack, &RuleSet::standard()), Ok(None));
+    }
+
+    // SPECIAL JOKERS
+
+    #[test]
+    fn test_win_with_double_joker() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+        ];
+        let rules = RuleSet::special_jokers();
+        let solution = can_win(&[], &rack, &rules).unwrap().unwrap();
+        let expected = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+        ]];
+        assert_eq!(solution, expected);
+
+        let rules = RuleSet {
+            bag: TileBag::special_jokers(),
+            ..RuleSet::standard()
+        };
+        assert_eq!(can_win(&[], &rack, &rules), Ok(None));
+    }
+
+    #[test]
+    fn test_win_with_mirror_joker() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+        ]];
+        let rack = vec![
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+        ];
+        let rules = RuleSet::special_jokers();
+        let solution = can_win(&board, &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &board, &rack, &rules);
+        assert_eq!(solution.len(), 1);
+    }
+
+    #[test]
+    fn test_win_with_mirror_facing_double_jokers() {
+        let rack = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 5).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+        ];
+        let rules = RuleSet::special_jokers();
+        let solution = can_win(&[], &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &[], &rack, &rules);
+    }
+
+    #[test]
+    fn test_win_with_color_change_jokers() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
+        ];
+        let rules = RuleSet::special_jokers();
+        let solution = can_win(&[], &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &[], &rack, &rules);
+    }
+
+    #[test]
+    fn test_win_with_jokers_only_special_set() {
+        let rack = vec![
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+        ];
+        let rules = RuleSet::special_jokers();
+        let solution = can_win(&[], &rack, &rules).unwrap().unwrap();
+        assert_plays(&solution, &[], &rack, &rules);
+    }
+
+    #[test]
+    fn test_cannot_win_with_disallowed_joker() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+        ];
+        let mut rules = RuleSet::special_jokers();
+        assert!(can_win(&[], &rack, &rules).unwrap().is_some());
+        rules.color_change_jokers = false;
+        assert_eq!(can_win(&[], &rack, &rules), Ok(None));
+    }
+
+    // BEST PLAY
+
+    #[test]
+    fn test_best_play_empties_rack() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ]];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        let play = best_play(&board, &rack, &rules, Objective::Tiles).unwrap();
+        assert_plays(&play.board, &board, &rack, &rules);
+        assert!(play.rack.is_empty());
+    }
+
+    #[test]
+    fn test_best_play_keeps_unplayable_tiles() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ]];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 2).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        let play = best_play(&board, &rack, &rules, Objective::Tiles).unwrap();
+        let played = vec![Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap())];
+        assert_plays(&play.board, &board, &played, &rules);
+        let expected = vec![
+            Tile::Basic(BasicTile::new(TileColor::Orange, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+        ];
+        assert_eq!(play.rack, expected);
+    }
+
+    #[test]
+    fn test_best_play_nothing_to_play() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ]];
+        let rack = vec![Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap())];
+        let play = best_play(&board, &rack, &RuleSet::standard(), Objective::Points).unwrap();
+        assert_eq!(play.board, board);
+        assert_eq!(play.rack, rack);
+    }
+
+    #[test]
+    fn test_best_play_objectives() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 13).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        let rules = RuleSet::standard();
+
+        // Six tiles worth 66 points.
+        let play = best_play(&[], &rack, &rules, Objective::Tiles).unwrap();
+        assert_eq!(
+            play.rack,
+            vec![Tile::Basic(BasicTile::new(TileColor::Black, 13).unwrap())]
+        );
+
+        // Three tiles worth 73 points.
+        let play = best_play(&[], &rack, &rules, Objective::Points).unwrap();
+        let expected = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+        ];
+        assert_eq!(play.rack, expected);
+    }
+
+    #[test]
+    fn test_best_play_objectives_with_high_penalties() {
+        let rules = RuleSet {
+            penalties: Penalties {
+                single_joker: u32::MAX,
+                ..Penalties::standard()
+            },
+            ..RuleSet::standard()
+        };
+        let config = SolveConfig::default();
+        let joker = Tile::Joker(Joker::new(JokerVariant::Single));
+        let jokers = _count(&[joker, joker]);
+        let tiles = _count(&[
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+        ]);
+        let search = Search::new(&[], &rules, Objective::Tiles, &config);
+        assert!(search.score(&tiles) > search.score(&jokers));
+        let search = Search::new(&[], &rules, Objective::Points, &config);
+        assert!(search.score(&tiles) < search.score(&jokers));
+    }
+
+    #[test]
+    fn test_scores_compare_measures_first() {
+        let tiles = (SLOTS * 255) as Measure;
+        let top = (tiles * u32::MAX as Measure) << MEASURE_BITS | tiles;
+        assert_eq!(_measure(_score(top, u32::MAX)), top);
+        assert!(_score(top - 1, u32::MAX) < _score(top, 0));
+    }
+
+    // DISRUPTION
+
+    #[test]
+    fn test_best_play_leaves_board_in_place() {
+        let board = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+            ],
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            ],
+        ];
+        let rack = vec![Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap())];
+        let rules = RuleSet::standard();
+        let play = best_play(&board, &rack, &rules, Objective::Tiles).unwrap();
+        let expected = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+            ],
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            ],
+        ];
+        assert_eq!(play.board, expected);
+        assert_eq!(play.disruption, Disruption::default());
+    }
+
+    #[test]
+    fn test_best_play_splits_one_set() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+        ]];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        let play = best_play(&board, &rack, &rules, Objective::Tiles).unwrap();
+        assert_plays(&play.board, &board, &rack, &rules);
+        assert_eq!(play.disruption, Disruption { sets: 1, tiles: 1 });
+    }
+
+    #[test]
+    fn test_disruption_split() {
+        let before = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+        ]];
+        let after = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            ],
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+            ],
+        ];
+        assert_eq!(
+            disruption(&before, &after),
+            Disruption { sets: 1, tiles: 0 }
+        );
+    }
+
+    #[test]
+    fn test_disruption_join() {
+        let before = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+            ],
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            ],
+        ];
+        let after = vec![before.concat()];
+        assert_eq!(
+            disruption(&before, &after),
+            Disruption { sets: 1, tiles: 3 }
+        );
+    }
+
+    // INITIAL MELD
+
+    #[test]
+    fn test_initial_meld_below_threshold() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 3).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 7).unwrap()),
+        ];
+        assert_eq!(initial_meld(&[], &rack, &RuleSet::standard()), Ok(None));
+    }
+
+    #[test]
+    fn test_initial_meld_keeps_board() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 3).unwrap()),
+        ]];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Blue, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 10).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+        ];
+        let play = initial_meld(&board, &rack, &RuleSet::standard())
+            .unwrap()
+            .unwrap();
+        let meld = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 10).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+        ];
+        assert_eq!(play.board, vec![board[0].clone(), meld]);
+        assert_eq!(
+            play.rack,
+            vec![Tile::Basic(BasicTile::new(TileColor::Blue, 4).unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_initial_meld_plays_most_tiles() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 3).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 10).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        let play = initial_meld(&[], &rack, &rules).unwrap().unwrap();
+        assert_plays(&play.board, &[], &rack, &rules);
+        assert!(play.rack.is_empty());
+    }
+
+    #[test]
+    fn test_initial_meld_counts_jokers() {
+        let rack = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+        ];
+        let play = initial_meld(&[], &rack, &RuleSet::standard())
+            .unwrap()
+            .unwrap();
+        let expected = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 11).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 12).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+        ];
+        assert_eq!(play.board, vec![expected]);
+    }
+
+    #[test]
+    fn test_initial_meld_with_double_joker() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 9).unwrap()),
+        ];
+        let rules = RuleSet::special_jokers();
+        assert!(initial_meld(&[], &rack, &rules).unwrap().is_some());
+
+        let rules = RuleSet {
+            initial_meld: 31,
+            ..rules
+        };
+        assert_eq!(initial_meld(&[], &rack, &rules), Ok(None));
+    }
+
+    // SOLUTIONS
+
+    fn two_runs() -> Vec<Tile> {
+        (1..=6)
+            .chain(1..=6)
+            .map(|value| Tile::Basic(BasicTile::new(TileColor::Red, value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_solutions() {
+        let rack = two_runs();
+        let rules = RuleSet::standard();
+        let found = solutions(&[], &rack, &rules, 10).unwrap();
+        let mut lengths: Vec<Vec<usize>> = found
+            .iter()
+            .map(|solution| solution.iter().map(Vec::len).collect())
+            .collect();
+        lengths.sort();
+        assert_eq!(lengths, vec![vec![3, 3, 3, 3], vec![3, 6, 3], vec![6, 6]]);
+        for solution in &found {
+            assert_plays(solution, &[], &rack, &rules);
+        }
+        assert_eq!(count_solutions(&[], &rack, &rules, 10), Ok(3));
+    }
+
+    #[test]
+    fn test_solutions_limit() {
+        let rack = two_runs();
+        let rules = RuleSet::standard();
+        assert_eq!(solutions(&[], &rack, &rules, 2).unwrap().len(), 2);
+        assert_eq!(count_solutions(&[], &rack, &rules, 2), Ok(2));
+        assert_eq!(solutions(&[], &rack, &rules, 0), Ok(vec![]));
+        assert_eq!(count_solutions(&[], &rack, &rules, 0), Ok(0));
+    }
+
+    #[test]
+    fn test_solutions_ignore_joker_placement() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ]];
+        let rack = vec![Tile::Joker(Joker::new(JokerVariant::Single))];
+        let rules = RuleSet::standard();
+        assert_eq!(count_solutions(&board, &rack, &rules, 10), Ok(1));
+    }
+
+    #[test]
+    fn test_no_solutions() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ];
+        let rules = RuleSet::standard();
+        assert_eq!(solutions(&[], &rack, &rules, 10), Ok(vec![]));
+        assert_eq!(count_solutions(&[], &rack, &rules, 10), Ok(0));
+    }
+
+    // LIMITS
+
+    fn limits_position() -> (Vec<Vec<Tile>>, Vec<Tile>) {
+        let board = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            ],
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 8).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
+            ],
+        ];
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 10).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 1).unwrap()),
+        ];
+        (board, rack)
+    }
+
+    #[test]
+    fn test_best_play_without_limits_is_proven() {
+        let (board, rack) = limits_position();
+        let rules = RuleSet::standard();
+        let play = best_play(&board, &rack, &rules, Objective::Tiles).unwrap();
+        assert!(play.proven);
+        assert_eq!(
+            play.rack,
+            vec![Tile::Basic(BasicTile::new(TileColor::Black, 1).unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_best_play_within_node_limits() {
+        let (board, rack) = limits_position();
+        let rules = RuleSet::standard();
+        let best = best_play(&board, &rack, &rules, Objective::Tiles).unwrap();
+        for limit in 0..20 {
+            let config = SolveConfig {
+                node_limit: Some(limit),
+                ..SolveConfig::default()
+            };
+            let play = best_play_with(&board, &rack, &rules, Objective::Tiles, &config).unwrap();
+            let mut played = rack.clone();
+            for tile in &play.rack {
+                let index = played.iter().position(|other| other == tile).unwrap();
+                played.remove(index);
+            }
+            assert_plays(&play.board, &board, &played, &rules);
+            assert!(play.rack.len() >= best.rack.len());
+            if play.proven {
+                assert_eq!(play, best);
+            }
+        }
+        let config = SolveConfig {
+            node_limit: Some(1_000_000),
+            ..SolveConfig::default()
+        };
+        let play = best_play_with(&board, &rack, &rules, Objective::Tiles, &config).unwrap();
+        assert_eq!(play, best);
+    }
+
+    #[test]
+    fn test_best_play_cancelled() {
+        let (board, rack) = limits_position();
+        let rules = RuleSet::standard();
+        let cancel = Cancel::new();
+        cancel.cancel();
+        let config = SolveConfig {
+            cancel: Some(cancel.clone()),
+            ..SolveConfig::default()
+        };
+        let play = best_play_with(&board, &rack, &rules, Objective::Tiles, &config).unwrap();
+        assert!(!play.proven);
+        assert_eq!(play.board, board);
+        assert_eq!(play.rack, rack);
+        assert!(cancel.is_cancelled());
+    }
+
+    #[test]
+    fn test_can_win_timeout() {
+        let (board, rack) = limits_position();
+        let rules = RuleSet::standard();
+        let config = SolveConfig {
+            time_limit: Some(Duration::from_secs(0)),
+            ..SolveConfig::default()
+        };
+        assert_eq!(
+            can_win_with(&board, &rack, &rules, &config),
+            Err(SolveError::Timeout)
+        );
+    }
+
+    // THREADS
+
+    #[test]
+    fn test_best_play_same_on_threads() {
+        let board = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Blue, 7).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
+            ],
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Black, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Orange, 5).unwrap()),
+            ],
+        ];
+        let rack = vec![
+            Tile::Joker(Joker::new(JokerVariant::Mirror)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Orange, 12).unwrap()),
+        ];
+        let rules = RuleSet::special_jokers();
+        let best = best_play(&board, &rack, &rules, Objective::Tiles).unwrap();
+        for threads in 2..6 {
+            let config = SolveConfig {
+                threads,
+                ..SolveConfig::default()
+            };
+            let play = best_play_with(&board, &rack, &rules, Objective::Tiles, &config).unwrap();
+            assert_eq!(play, best);
+        }
+    }
+
+    #[test]
+    fn test_can_win_on_threads() {
+        let rack = vec![
+            Tile::Basic(BasicTile::new(TileColor::Black, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Joker(Joker::new(JokerVariant::ColorChange)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Black, 8).unwrap()),
+        ];
+        let rules = RuleSet::special_jokers();
+        let config = SolveConfig {
+            threads: 4,
+            ..SolveConfig::default()
+        };
+        assert_eq!(
+            can_win_with(&[], &rack, &rules, &config),
+            can_win(&[], &rack, &rules)
+        );
+    }
+
+    #[test]
+    fn test_best_play_on_threads_within_node_limits() {
+        let (board, rack) = limits_position();
+        let rules = RuleSet::standard();
+        for limit in 0..20 {
+            let config = SolveConfig {
+                node_limit: Some(limit),
+                threads: 3,
+                ..SolveConfig::default()
+            };
+            let play = best_play_with(&board, &rack, &rules, Objective::Tiles, &config).unwrap();
+            let mut played = rack.clone();
+            for tile in &play.rack {
+                let index = played.iter().position(|other| other == tile).unwrap();
+                played.remove(index);
+            }
+            assert_plays(&play.board, &board, &played, &rules);
+        }
+    }
+
+    #[test]
+    fn test_can_win_on_threads_within_node_limits() {
+        let (board, mut rack) = limits_position();
+        rack.pop();
+        rack.push(Tile::Basic(BasicTile::new(TileColor::Blue, 11).unwrap()));
+        let rules = RuleSet::standard();
+        let mut kept = false;
+        for limit in 0..10 {
+            let single = SolveConfig {
+                node_limit: Some(limit),
+                ..SolveConfig::default()
+            };
+            let timed_out = can_win_with(&board, &rack, &rules, &single).is_err();
+            for threads in 2..5 {
+                let config = SolveConfig {
+                    threads,
+                    ..single.clone()
+                };
+                match can_win_with(&board, &rack, &rules, &config) {
+                    Ok(Some(solution)) => {
+                        assert_plays(&solution, &board, &rack, &rules);
+                        kept |= timed_out;
+                    }
+                    result => assert_eq!(result, Err(SolveError::Timeout)),
+                }
+            }
+        }
+        // Some search on several threads timed out after finding a win, where a single thread
+        // found none within the same limit.
+        assert!(kept);
+    }
+
+    // SET TABLE
+
+    #[test]
+    fn test_set_table_sets_are_valid() {
+        for rules in &[RuleSet::standard(), RuleSet::special_jokers()] {
+            let table = SetTable::get(rules);
+            for slot in 0..SLOTS {
+                let listed = table.lowest(slot);
+                let mut found = Vec::new();
+                listed
+                    .tree
+                    .find(&[TileBag::BASIC_COPIES; SLOTS], &mut found);
+                assert_eq!(found.len(), listed.sets.len());
+                for set in &listed.sets {
+                    assert!(validate_set(set, rules).is_ok(), "invalid set {:?}", set);
+                    assert_eq!(_lowest(&_count(set)), Some(slot));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_table_matches_candidates() {
+        let mut tiles = vec![
+            Tile::Joker(Joker::new(JokerVariant::Single)),
+            Tile::Joker(Joker::new(JokerVariant::Double)),
+        ];
+        for value in 3..=9 {
+            tiles.push(Tile::Basic(BasicTile::new(TileColor::Red, value).unwrap()));
+            tiles.push(Tile::Basic(BasicTile::new(TileColor::Blue, value).unwrap()));
+        }
+        tiles.push(Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()));
+        tiles.push(Tile::Basic(BasicTile::new(TileColor::Black, 5).unwrap()));
+        for rules in &[RuleSet::standard(), RuleSet::special_jokers()] {
+            let table = SetTable::get(rules);
+            let mut counts = _count(&tiles);
+            while let Some(lowest) = _lowest(&counts) {
+                let expected = _candidates(&counts, lowest, rules);
+                let actual = table.candidates(&counts, lowest);
+                let mut expected: Vec<_> = expected.iter().map(_count).collect();
+                let mut actual: Vec<_> = actual.iter().map(_count).collect();
+                expected.sort();
+                actual.sort();
+                assert_eq!(actual, expected);
+                counts[lowest] -= 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_table_shared_and_evicted() {
+        let rules = RuleSet {
+            min_set_size: 20,
+            ..RuleSet::standard()
+        };
+        let table = SetTable::get(&rules);
+        let scored = RuleSet {
+            initial_meld: 0,
+            penalties: Penalties {
+                single_joker: 50,
+                ..Penalties::standard()
+            },
+            ..rules.clone()
+        };
+        assert!(Arc::ptr_eq(&table, &SetTable::get(&scored)));
+        for size in 21..21 + TABLES_KEPT as u8 {
+            SetTable::get(&RuleSet {
+                min_set_size: size,
+                ..rules.clone()
+            });
+        }
+        assert!(TABLES.lock().unwrap().len() <= TABLES_KEPT);
+        assert!(!Arc::ptr_eq(&table, &SetTable::get(&rules)));
+    }
+
+    // ERRORS
+
+    #[test]
+    fn test_invalid_board() {
+        let board = vec![
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
+            ],
+            vec![
+                Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Blue, 5).unwrap()),
+                Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+            ],
+        ];
+        let error = InvalidSet {
+            reason: InvalidReason::ColorMismatchInRun,
+            index: 1,
+        };
+        assert_eq!(
+            can_win(&board, &[], &RuleSet::standard()),
+            Err(SolveError::InvalidBoardSet { set: 1, error })
+        );
+    }
+
+    #[test]
+    fn test_invalid_rack_tile() {
+        let rack = vec![Tile::Basic(BasicTile {
+            color: TileColor::Red,
+            value: 14,
+        })];
+        assert_eq!(
+            best_play(&[], &rack, &RuleSet::standard(), Objective::Tiles),
+            Err(SolveError::InvalidRackTile(InvalidTileValue { value: 14 }))
+        );
+    }
+
+    #[test]
+    fn test_too_many_copies() {
+        let board = vec![vec![
+            Tile::Basic(BasicTile::new(TileColor::Red, 4).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 6).unwrap()),
+        ]];
+        let rack = vec![Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()); 2];
+        let error = TooManyCopies {
+            tile: Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            available: 2,
+        };
+        assert_eq!(
+            can_win(&board, &rack, &RuleSet::standard()),
+            Err(SolveError::TooManyCopies(error))
+        );
+    }
 }
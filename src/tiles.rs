@@ -1,28 +1,47 @@
 /// Copyright (c) 2020, Shoyo Inokuchi
+use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt;
+use std::iter::{self, Peekable};
+use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Tile {
     Basic(BasicTile),
     Joker(Joker),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BasicTile {
     pub color: TileColor,
     pub value: TileValue,
 }
 
 impl BasicTile {
-    pub fn new(color: TileColor, value: TileValue) -> Self {
+    /// Create a basic tile, or return an error if the value is not between 1 and 13.
+    pub fn new(color: TileColor, value: TileValue) -> Result<Self, InvalidTileValue> {
         if value == 0 || value > 13 {
-            panic!("Attempted to create a tile with an invalid value {}", value);
+            return Err(InvalidTileValue { value });
         }
-        Self { color, value }
+        Ok(Self { color, value })
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+/// A tile value outside of 1 to 13.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidTileValue {
+    pub value: TileValue,
+}
+
+impl fmt::Display for InvalidTileValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tile value {} is not between 1 and 13", self.value)
+    }
+}
+
+impl Error for InvalidTileValue {}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TileColor {
     Black,
     Red,
@@ -43,7 +62,7 @@ impl fmt::Display for TileColor {
 
 pub type TileValue = u8;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Joker {
     pub variant: JokerVariant,
 }
@@ -54,7 +73,7 @@ impl Joker {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum JokerVariant {
     Single,
     Double,
@@ -73,18 +92,395 @@ impl fmt::Display for JokerVariant {
     }
 }
 
-/// Utilities
+/// The contents of a box of Rummikub tiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileBag {
+    /// `single_jokers`, `double_jokers`, `mirror_jokers` and `color_change_jokers` store the number
+    /// of jokers of each variant in the box.
+    pub single_jokers: u8,
+    pub double_jokers: u8,
+    pub mirror_jokers: u8,
+    pub color_change_jokers: u8,
+}
+
+impl TileBag {
+    /// The number of copies of every basic tile in the box.
+    pub const BASIC_COPIES: u8 = 2;
+
+    /// The box of the original game, with two single jokers.
+    pub fn standard() -> Self {
+        Self {
+            single_jokers: 2,
+            double_jokers: 0,
+            mirror_jokers: 0,
+            color_change_jokers: 0,
+        }
+    }
+
+    /// The box of the special jokers edition, with two jokers of every variant.
+    pub fn special_jokers() -> Self {
+        Self {
+            single_jokers: 2,
+            double_jokers: 2,
+            mirror_jokers: 2,
+            color_change_jokers: 2,
+        }
+    }
+
+    /// Return the number of copies of the given tile in the box, which is 0 for a basic tile with a
+    /// value outside of 1 to 13.
+    pub fn copies(&self, tile: &Tile) -> u8 {
+        match tile {
+            Tile::Basic(t) if BasicTile::new(t.color, t.value).is_err() => 0,
+            Tile::Basic(_) => Self::BASIC_COPIES,
+            Tile::Joker(j) => match j.variant {
+                JokerVariant::Single => self.single_jokers,
+                JokerVariant::Double => self.double_jokers,
+                JokerVariant::Mirror => self.mirror_jokers,
+                JokerVariant::ColorChange => self.color_change_jokers,
+            },
+        }
+    }
+
+    /// Check that the given tiles can all be taken from the box at once, and return the first tile
+    /// with more copies than the box contains otherwise. The box contains no copies of a basic tile
+    /// with an invalid value.
+    pub fn check<'a, I>(&self, tiles: I) -> Result<(), TooManyCopies>
+    where
+        I: IntoIterator<Item = &'a Tile>,
+    {
+        let mut counts = [0usize; SLOTS];
+        for tile in tiles {
+            let count = match _slot(tile) {
+                Ok(slot) => {
+                    counts[slot] += 1;
+                    counts[slot]
+                }
+                Err(_) => 1,
+            };
+            if count > self.copies(tile) as usize {
+                return Err(TooManyCopies {
+                    tile: *tile,
+                    available: self.copies(tile),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that every tile on the board can be taken from the box at once.
+    pub fn check_board(&self, board: &[Vec<Tile>]) -> Result<(), TooManyCopies> {
+        self.check(board.iter().flatten())
+    }
+
+    /// Check that every tile on the board and in the rack can be taken from the box at once.
+    pub fn check_position(&self, board: &[Vec<Tile>], rack: &[Tile]) -> Result<(), TooManyCopies> {
+        self.check(board.iter().flatten().chain(rack.iter()))
+    }
+}
+
+/// The points that tiles left on a rack cost at the end of a round. Basic tiles cost their value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Penalties {
+    /// `single_joker`, `double_joker`, `mirror_joker` and `color_change_joker` store the points a
+    /// joker of each variant costs.
+    pub single_joker: u32,
+    pub double_joker: u32,
+    pub mirror_joker: u32,
+    pub color_change_joker: u32,
+}
+
+impl Penalties {
+    /// The penalties of the original game, in which every joker costs 30 points.
+    pub fn standard() -> Self {
+        Self {
+            single_joker: 30,
+            double_joker: 30,
+            mirror_joker: 30,
+            color_change_joker: 30,
+        }
+    }
+
+    /// Return the points the given tile costs when left on a rack.
+    pub fn tile(&self, tile: &Tile) -> u32 {
+        match tile {
+            Tile::Basic(t) => t.value as u32,
+            Tile::Joker(j) => match j.variant {
+                JokerVariant::Single => self.single_joker,
+                JokerVariant::Double => self.double_joker,
+                JokerVariant::Mirror => self.mirror_joker,
+                JokerVariant::ColorChange => self.color_change_joker,
+            },
+        }
+    }
 
-/// Convert a string containing space-limited tile abbreviations (such as r5 - Red 5 tile, j - Single
-/// Joker tile, etc.) and return a vector of the corresponding set.
+    /// Return the points the tiles left on a rack cost together.
+    pub fn rack(&self, rack: &[Tile]) -> u32 {
+        rack.iter().map(|tile| self.tile(tile)).sum()
+    }
+}
+
+/// A tile used more times than the box contains copies of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TooManyCopies {
+    pub tile: Tile,
+    pub available: u8,
+}
+
+impl fmt::Display for TooManyCopies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match &self.tile {
+            Tile::Basic(t) => format!("{} {}", t.color, t.value),
+            Tile::Joker(j) => format!("{} joker", j.variant),
+        };
+        write!(
+            f,
+            "the box only contains {} copies of the {} tile",
+            self.available, name
+        )
+    }
+}
+
+impl Error for TooManyCopies {}
+
+/// A kind of tile stored in one byte. Ids are ordered like tiles: basic tiles by color and then
+/// value, followed by the single, double, mirror and color-change jokers.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TileId(u8);
+
+impl TileId {
+    /// The number of distinct ids.
+    pub const COUNT: usize = SLOTS;
+
+    /// Return the id of the given tile, or an error if it is a basic tile with a value outside of 1
+    /// to 13.
+    pub fn new(tile: &Tile) -> Result<Self, InvalidTileValue> {
+        Ok(Self(_slot(tile)? as u8))
+    }
+
+    /// Return the id with the given index, or None if the index is not below `TileId::COUNT`.
+    pub fn from_index(index: usize) -> Option<Self> {
+        if index < Self::COUNT {
+            Some(Self(index as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn tile(self) -> Tile {
+        _tile_at(self.index())
+    }
+
+    /// Return every id in order.
+    pub fn all() -> impl Iterator<Item = TileId> {
+        (0..Self::COUNT as u8).map(Self)
+    }
+}
+
+impl TryFrom<Tile> for TileId {
+    type Error = InvalidTileValue;
+
+    fn try_from(tile: Tile) -> Result<Self, Self::Error> {
+        Self::new(&tile)
+    }
+}
+
+impl From<TileId> for Tile {
+    fn from(id: TileId) -> Self {
+        id.tile()
+    }
+}
+
+/// A multiset of tiles, such as a rack or the tiles on a board, stored as the number of copies of
+/// every kind of tile. Multisets holding the same tiles compare equal whatever order the tiles
+/// were added in. A multiset holds at most 255 copies of every kind of tile, and copies added
+/// beyond that are dropped.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TileMultiset {
+    counts: [u8; SLOTS],
+}
+
+impl TileMultiset {
+    pub fn new() -> Self {
+        Self { counts: [0; SLOTS] }
+    }
+
+    /// Return the multiset of the given tiles, or an error for the first basic tile with a value
+    /// outside of 1 to 13.
+    pub fn from_tiles<'a, I>(tiles: I) -> Result<Self, InvalidTileValue>
+    where
+        I: IntoIterator<Item = &'a Tile>,
+    {
+        let mut multiset = Self::new();
+        for tile in tiles {
+            multiset.insert(tile)?;
+        }
+        Ok(multiset)
+    }
+
+    /// Return the number of copies of the given tile, which is 0 for a basic tile with an invalid
+    /// value.
+    pub fn count(&self, tile: &Tile) -> u8 {
+        _slot(tile).map_or(0, |slot| self.counts[slot])
+    }
+
+    /// Add one copy of the given tile, or return an error if it is a basic tile with an invalid
+    /// value. The copy is dropped if the multiset already holds 255 copies of the tile.
+    pub fn insert(&mut self, tile: &Tile) -> Result<(), InvalidTileValue> {
+        let count = &mut self.counts[_slot(tile)?];
+        *count = count.saturating_add(1);
+        Ok(())
+    }
+
+    /// Remove one copy of the given tile, and return whether there was one to remove.
+    pub fn remove(&mut self, tile: &Tile) -> bool {
+        let count = match _slot(tile) {
+            Ok(slot) => &mut self.counts[slot],
+            Err(_) => return false,
+        };
+        if *count == 0 {
+            return false;
+        }
+        *count -= 1;
+        true
+    }
+
+    /// Return the number of tiles, counting every copy.
+    pub fn len(&self) -> usize {
+        self.counts.iter().map(|&count| count as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&count| count == 0)
+    }
+
+    /// Return whether every copy of every tile in the multiset is also in the other one.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        (0..SLOTS).all(|slot| self.counts[slot] <= other.counts[slot])
+    }
+
+    /// Return the tiles of both multisets together, keeping at most 255 copies of every tile.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut counts = self.counts;
+        for (count, &more) in counts.iter_mut().zip(other.counts.iter()) {
+            *count = count.saturating_add(more);
+        }
+        Self { counts }
+    }
+
+    /// Return the copies of tiles shared by both multisets.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut counts = self.counts;
+        for (count, &other) in counts.iter_mut().zip(other.counts.iter()) {
+            *count = (*count).min(other);
+        }
+        Self { counts }
+    }
+
+    /// Return the tiles left after taking the other multiset out of this one, or None if the
+    /// other multiset is not a subset of this one.
+    pub fn difference(&self, other: &Self) -> Option<Self> {
+        if !other.is_subset(self) {
+            return None;
+        }
+        let mut counts = self.counts;
+        for (count, &less) in counts.iter_mut().zip(other.counts.iter()) {
+            *count -= less;
+        }
+        Some(Self { counts })
+    }
+
+    /// Return every copy of every tile, ordered by tile id.
+    pub fn iter(&self) -> impl Iterator<Item = Tile> + '_ {
+        TileId::all()
+            .flat_map(move |id| iter::repeat_n(id.tile(), self.counts[id.index()] as usize))
+    }
+
+    pub fn to_vec(&self) -> Vec<Tile> {
+        self.iter().collect()
+    }
+
+    /// Return the number of copies of every kind of tile, indexed by slot.
+    pub(crate) fn counts(&self) -> &[u8; SLOTS] {
+        &self.counts
+    }
+}
+
+impl Default for TileMultiset {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The number of distinct kinds of tile: 52 basic tiles followed by the 4 joker variants.
+pub(crate) const SLOTS: usize = 56;
+
+/// Return a distinct slot for every kind of tile, for counting copies of tiles, or an error if the
+/// tile is a basic tile with a value outside of 1 to 13.
+pub(crate) fn _slot(tile: &Tile) -> Result<usize, InvalidTileValue> {
+    match tile {
+        Tile::Basic(t) => {
+            let t = BasicTile::new(t.color, t.value)?;
+            let color = match t.color {
+                TileColor::Black => 0,
+                TileColor::Red => 1,
+                TileColor::Blue => 2,
+                TileColor::Orange => 3,
+            };
+            Ok(color * 13 + t.value as usize - 1)
+        }
+        Tile::Joker(j) => Ok(match j.variant {
+            JokerVariant::Single => 52,
+            JokerVariant::Double => 53,
+            JokerVariant::Mirror => 54,
+            JokerVariant::ColorChange => 55,
+        }),
+    }
+}
+
+/// Return the kind of tile stored in the given slot. Inverse of `_slot`.
+pub(crate) fn _tile_at(slot: usize) -> Tile {
+    const COLORS: [TileColor; 4] = [
+        TileColor::Black,
+        TileColor::Red,
+        TileColor::Blue,
+        TileColor::Orange,
+    ];
+    const VARIANTS: [JokerVariant; 4] = [
+        JokerVariant::Single,
+        JokerVariant::Double,
+        JokerVariant::Mirror,
+        JokerVariant::ColorChange,
+    ];
+    if slot < 52 {
+        Tile::Basic(BasicTile {
+            color: COLORS[slot / 13],
+            value: (slot % 13) as TileValue + 1,
+        })
+    } else {
+        Tile::Joker(Joker::new(VARIANTS[slot - 52]))
+    }
+}
+
+// Utilities
+
+/// Convert a string containing tile abbreviations (such as r5 - Red 5 tile, j - Single Joker tile,
+/// etc.) and return a vector of the corresponding set. Tiles are separated by any whitespace or
+/// commas, and letters may be in either case. Jokers may carry a declaration as read by
+/// `deserialize_declared_set`, which is left out of the set.
 ///
 /// Abbreviations:
 ///
-/// Basic tiles: <tile color><tile value>
-///     Red    --> "r"
-///     Orange --> "o"
-///     Black  --> "a"
-///     Blue   --> "u"
+/// Basic tiles: <tile color><tile value>, where the color may also be spelled out in full and be
+/// followed by whitespace before the value.
+///     Red    --> "r" or "red"
+///     Orange --> "o" or "orange"
+///     Black  --> "a" or "black"
+///     Blue   --> "u" or "blue"
 ///
 /// Jokers: <joker type>
 ///     Single Joker      --> "j"
@@ -95,78 +491,409 @@ impl fmt::Display for JokerVariant {
 /// Examples:
 ///     "r1 r2 r3"
 ///     "a6 c u8 u9 m j u8 c a6"
-pub fn deserialize_set(input: &str) -> Result<Vec<Tile>, String> {
-    let mut stream = input.split(' ');
-    let mut vec = Vec::new();
-    while let Some(token) = stream.next() {
-        match token.chars().nth(0).unwrap() {
-            'r' => {
-                let val = match parse_tile_value(&token[1..]) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                vec.push(Tile::Basic(BasicTile::new(TileColor::Red, val)));
-            }
-            'o' => {
-                let val = match parse_tile_value(&token[1..]) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                vec.push(Tile::Basic(BasicTile::new(TileColor::Orange, val)));
-            }
-            'u' => {
-                let val = match parse_tile_value(&token[1..]) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                vec.push(Tile::Basic(BasicTile::new(TileColor::Blue, val)));
-            }
-            'a' => {
-                let val = match parse_tile_value(&token[1..]) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                vec.push(Tile::Basic(BasicTile::new(TileColor::Black, val)));
-            }
-            'j' => {
-                if token.len() > 1 {
-                    return Err(format!("Unrecognized token {}. Did you mean 'j'?", token));
-                }
-                vec.push(Tile::Joker(Joker::new(JokerVariant::Single)));
-            }
-            'd' => {
-                if token.len() > 1 {
-                    return Err(format!("Unrecognized token {}. Did you mean 'd'?", token));
-                }
-                vec.push(Tile::Joker(Joker::new(JokerVariant::Double)));
-            }
-            'm' => {
-                if token.len() > 1 {
-                    return Err(format!("Unrecognized token {}. Did you mean 'm'?", token));
-                }
-                vec.push(Tile::Joker(Joker::new(JokerVariant::Mirror)));
+///     "Red5, blue 12, J"
+pub fn deserialize_set(input: &str) -> Result<Vec<Tile>, ParseError> {
+    _tiles(input)
+        .map(|tile| tile.map(|(_, tile, _)| tile))
+        .collect()
+}
+
+/// Same as `deserialize_set`, but also return the tiles that jokers are declared to stand for.
+/// A declaration follows the joker after "=", with the declared tiles written without spaces.
+/// Only single and double jokers can be declared.
+///
+/// Examples:
+///     "r5 j=r6 r7"
+///     "u3 d=u4u5 u6"
+pub fn deserialize_declared_set(input: &str) -> Result<DeclaredSet, ParseError> {
+    let mut set = DeclaredSet::default();
+    for tile in _tiles(input) {
+        let (_, tile, declaration) = tile?;
+        if let Some(declaration) = declaration {
+            set.declarations.push((set.tiles.len(), declaration));
+        }
+        set.tiles.push(tile);
+    }
+    Ok(set)
+}
+
+/// Read the tiles written in the input one at a time, along with the byte offset of each and the
+/// tiles it is declared to stand for if it is a declared joker.
+fn _tiles(input: &str) -> impl Iterator<Item = Result<Read, ParseError>> + '_ {
+    let mut words = _words(input).peekable();
+    iter::from_fn(move || {
+        let (offset, word) = words.next()?;
+        let read = match word.find('=') {
+            Some(equals) => _declared(offset, word, equals)
+                .map(|(joker, declaration)| (offset, joker, Some(declaration))),
+            None => _tile(input, offset, word, &mut words).map(|tile| (offset, tile, None)),
+        };
+        Some(read)
+    })
+}
+
+/// A tile read from the input, along with its byte offset and declaration.
+type Read = (usize, Tile, Option<Declaration>);
+
+/// Read a joker written along with the tiles it is declared to stand for, such as "j=r6".
+fn _declared(offset: usize, word: &str, equals: usize) -> Result<(Tile, Declaration), ParseError> {
+    let error = || ParseError {
+        offset,
+        token: word.to_string(),
+        kind: ParseErrorKind::InvalidDeclaration,
+        suggestion: None,
+    };
+    let lower = word.to_ascii_lowercase();
+    let variant = _joker(&lower[..equals]).ok_or_else(error)?;
+    let mut declared = Vec::new();
+    let mut rest = &lower[equals + 1..];
+    while !rest.is_empty() {
+        let digits = rest.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        let color = _color(&rest[..rest.len() - digits.len()]).ok_or_else(error)?;
+        rest = digits.trim_start_matches(|c: char| c.is_ascii_digit());
+        let value = digits[..digits.len() - rest.len()]
+            .parse()
+            .map_err(|_| error())?;
+        declared.push(BasicTile::new(color, value).map_err(|_| error())?);
+    }
+    let declaration = match (variant, declared.as_slice()) {
+        (JokerVariant::Single, &[tile]) => Declaration::Single(tile),
+        (JokerVariant::Double, &[first, second]) => Declaration::Double(first, second),
+        _ => return Err(error()),
+    };
+    Ok((Tile::Joker(Joker::new(variant)), declaration))
+}
+
+/// Read the tile written as the word at the given offset of the input, taking its value from the
+/// next word if the word is only a color.
+fn _tile<'a, I>(
+    input: &str,
+    offset: usize,
+    word: &str,
+    words: &mut Peekable<I>,
+) -> Result<Tile, ParseError>
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    let lower = word.to_ascii_lowercase();
+    let letters = lower.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &lower[letters.len()..];
+    let error = |kind, end: usize, suggestion| ParseError {
+        offset,
+        token: input[offset..end].to_string(),
+        kind,
+        suggestion,
+    };
+    if let Some(variant) = _joker(letters) {
+        if !digits.is_empty() {
+            let suggestion = Some(letters.to_string());
+            return Err(error(
+                ParseErrorKind::UnknownTile,
+                offset + word.len(),
+                suggestion,
+            ));
+        }
+        return Ok(Tile::Joker(Joker::new(variant)));
+    }
+    let color = match _color(letters) {
+        Some(color) => color,
+        None => {
+            let suggestion = _suggest(letters, digits);
+            return Err(error(
+                ParseErrorKind::UnknownTile,
+                offset + word.len(),
+                suggestion,
+            ));
+        }
+    };
+    let (end, digits) = match words.peek() {
+        _ if !digits.is_empty() => (offset + word.len(), digits),
+        Some(&(next, number)) if number.bytes().all(|b| b.is_ascii_digit()) => {
+            words.next();
+            (next + number.len(), number)
+        }
+        _ => {
+            return Err(error(
+                ParseErrorKind::MissingValue,
+                offset + word.len(),
+                None,
+            ))
+        }
+    };
+    digits
+        .parse::<TileValue>()
+        .ok()
+        .and_then(|value| BasicTile::new(color, value).ok())
+        .map(Tile::Basic)
+        .ok_or_else(|| error(ParseErrorKind::InvalidValue, end, None))
+}
+
+/// Convert a set into the abbreviations read by `deserialize_set`, separated by single spaces.
+/// Deserializing the result gives back the same set.
+///
+/// Example:
+///     [Red 5, Black 6, Single Joker, ColorChange Joker] --> "r5 a6 j c"
+pub fn serialize_set(set: &[Tile]) -> String {
+    set.iter()
+        .map(|tile| tile.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Tiles are displayed as their abbreviation, such as "r5" or "j".
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tile::Basic(t) => write!(f, "{}{}", _color_letter(t.color), t.value),
+            Tile::Joker(j) => write!(f, "{}", _joker_letter(j.variant)),
+        }
+    }
+}
+
+impl FromStr for Tile {
+    type Err = ParseError;
+
+    /// Read a single tile written as in `deserialize_set`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut tiles = _tiles(input);
+        match (tiles.next(), tiles.next()) {
+            (Some(tile), None) => tile.map(|(_, tile, _)| tile),
+            (None, _) => Err(ParseError {
+                offset: 0,
+                token: input.to_string(),
+                kind: ParseErrorKind::NotOneTile,
+                suggestion: None,
+            }),
+            (Some(_), Some(extra)) => {
+                let (offset, _, _) = extra?;
+                Err(ParseError {
+                    offset,
+                    token: input[offset..].trim_end().to_string(),
+                    kind: ParseErrorKind::NotOneTile,
+                    suggestion: None,
+                })
             }
-            'c' => {
-                if token.len() > 1 {
-                    return Err(format!("Unrecognized token {}. Did you mean 'c'?", token));
-                }
-                vec.push(Tile::Joker(Joker::new(JokerVariant::ColorChange)));
+        }
+    }
+}
+
+/// A set of tiles that is displayed and read in the notation of `serialize_set` and
+/// `deserialize_set`.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TileSet(pub Vec<Tile>);
+
+impl fmt::Display for TileSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serialize_set(&self.0))
+    }
+}
+
+impl FromStr for TileSet {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        deserialize_set(input).map(TileSet)
+    }
+}
+
+impl From<Vec<Tile>> for TileSet {
+    fn from(set: Vec<Tile>) -> Self {
+        TileSet(set)
+    }
+}
+
+impl From<TileSet> for Vec<Tile> {
+    fn from(set: TileSet) -> Self {
+        set.0
+    }
+}
+
+/// Convert a set into the notation read by `deserialize_declared_set`, writing every declared
+/// joker along with the tiles it is declared to stand for. Deserializing the result gives back the
+/// same set.
+///
+/// Example:
+///     [Red 5, Single Joker declared as Red 6, Red 7] --> "r5 j=r6 r7"
+pub fn serialize_declared_set(set: &DeclaredSet) -> String {
+    set.tiles
+        .iter()
+        .enumerate()
+        .map(|(index, tile)| match set.declaration(index) {
+            Some(declaration) => format!("{}={}", tile, declaration),
+            None => tile.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The tiles a joker is declared to stand for.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Declaration {
+    /// A single joker stands for one tile.
+    Single(BasicTile),
+    /// A double joker stands for two adjacent tiles, from left to right.
+    Double(BasicTile, BasicTile),
+}
+
+/// Declarations are displayed as the declared tiles without spaces, such as "u4u5".
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Declaration::Single(tile) => write!(f, "{}", Tile::Basic(tile)),
+            Declaration::Double(first, second) => {
+                write!(f, "{}{}", Tile::Basic(first), Tile::Basic(second))
             }
-            _ => return Err(format!("Unrecognized token {}", token)),
         }
     }
-    Ok(vec)
 }
 
-fn parse_tile_value(token: &str) -> Result<TileValue, String> {
-    let val = match token.parse::<TileValue>() {
-        Ok(v) => v,
-        Err(_) => return Err(format!("Invalid tile value in token: \"{}\"", token)),
-    };
-    if val == 0 || val > 13 {
-        return Err(format!("Invalid tile value in token: \"{}\"", token));
+/// A set along with the tiles that some of its jokers are declared to stand for, displayed and
+/// read in the notation of `serialize_declared_set` and `deserialize_declared_set`.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct DeclaredSet {
+    pub tiles: Vec<Tile>,
+    /// The declarations paired with the index of their joker in `tiles`.
+    pub declarations: Vec<(usize, Declaration)>,
+}
+
+impl DeclaredSet {
+    /// Return the declaration of the tile at the given index, if any.
+    pub fn declaration(&self, index: usize) -> Option<Declaration> {
+        self.declarations
+            .iter()
+            .find(|&&(at, _)| at == index)
+            .map(|&(_, declaration)| declaration)
+    }
+}
+
+impl fmt::Display for DeclaredSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serialize_declared_set(self))
+    }
+}
+
+impl FromStr for DeclaredSet {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        deserialize_declared_set(input)
+    }
+}
+
+/// An error found while reading tile notation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// `offset` stores the byte offset of the offending token in the input, and `token` the token
+    /// as written.
+    pub offset: usize,
+    pub token: String,
+    pub kind: ParseErrorKind,
+    /// A token that was probably meant instead, if there is one.
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The token does not stand for any tile.
+    UnknownTile,
+    /// A tile color is not followed by a value.
+    MissingValue,
+    /// A tile value is not between 1 and 13.
+    InvalidValue,
+    /// A single tile was expected, but the input holds none or several.
+    NotOneTile,
+    /// A declaration is not a single or double joker followed by as many basic tiles as the joker
+    /// stands for.
+    InvalidDeclaration,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let problem = match self.kind {
+            ParseErrorKind::UnknownTile => "unrecognized token",
+            ParseErrorKind::MissingValue => "missing tile value after",
+            ParseErrorKind::InvalidValue => "invalid tile value in token",
+            ParseErrorKind::NotOneTile => "expected a single tile in",
+            ParseErrorKind::InvalidDeclaration => "invalid joker declaration",
+        };
+        write!(f, "{} \"{}\" at byte {}", problem, self.token, self.offset)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean \"{}\"?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
+
+/// Split the input into words separated by whitespace or commas, along with the byte offset at
+/// which every word starts.
+fn _words(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - input.as_ptr() as usize, word))
+}
+
+/// Return the color written as the given lowercase letters, if any.
+fn _color(letters: &str) -> Option<TileColor> {
+    match letters {
+        "r" | "red" => Some(TileColor::Red),
+        "o" | "orange" => Some(TileColor::Orange),
+        "a" | "black" => Some(TileColor::Black),
+        "u" | "blue" => Some(TileColor::Blue),
+        _ => None,
+    }
+}
+
+/// Return the letter that stands for the color.
+fn _color_letter(color: TileColor) -> char {
+    match color {
+        TileColor::Red => 'r',
+        TileColor::Orange => 'o',
+        TileColor::Black => 'a',
+        TileColor::Blue => 'u',
+    }
+}
+
+/// Return the letter that stands for the joker variant.
+fn _joker_letter(variant: JokerVariant) -> char {
+    match variant {
+        JokerVariant::Single => 'j',
+        JokerVariant::Double => 'd',
+        JokerVariant::Mirror => 'm',
+        JokerVariant::ColorChange => 'c',
+    }
+}
+
+/// Return the joker variant written as the given lowercase letters, if any.
+fn _joker(letters: &str) -> Option<JokerVariant> {
+    match letters {
+        "j" => Some(JokerVariant::Single),
+        "d" => Some(JokerVariant::Double),
+        "m" => Some(JokerVariant::Mirror),
+        "c" => Some(JokerVariant::ColorChange),
+        _ => None,
+    }
+}
+
+/// Guess the token meant by an unrecognized one: the joker starting it, or the basic tile whose
+/// color name starts with its letters if there is only one.
+fn _suggest(letters: &str, digits: &str) -> Option<String> {
+    const NAMES: [(&str, &str); 4] = [("red", "r"), ("orange", "o"), ("black", "a"), ("blue", "u")];
+    if letters.is_empty() {
+        return None;
+    }
+    if digits.is_empty() {
+        if let Some(joker) = letters.get(..1).filter(|first| _joker(first).is_some()) {
+            return Some(joker.to_string());
+        }
+    }
+    let mut matches = NAMES.iter().filter(|(name, _)| name.starts_with(letters));
+    match (matches.next(), matches.next()) {
+        (Some((_, abbreviation)), None) => Some(format!("{}{}", abbreviation, digits)),
+        _ => None,
     }
-    Ok(val)
 }
 
 #[cfg(test)]
@@ -177,12 +904,12 @@ mod tests {
     fn test_vectorize_set_1() {
         let input = "r1 r2 r3 j d r7";
         let expected = vec![
-            Tile::Basic(BasicTile::new(TileColor::Red, 1)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 2)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 3)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 1).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 2).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Red, 3).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Single)),
             Tile::Joker(Joker::new(JokerVariant::Double)),
-            Tile::Basic(BasicTile::new(TileColor::Red, 7)),
+            Tile::Basic(BasicTile::new(TileColor::Red, 7).unwrap()),
         ];
         assert_eq!(deserialize_set(input).unwrap(), expected);
     }
@@ -191,16 +918,357 @@ mod tests {
     fn test_vectorize_set_2() {
         let input = "a6 c u8 u9 m j u8 c a6";
         let expected = vec![
-            Tile::Basic(BasicTile::new(TileColor::Black, 6)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 6).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 9)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 9).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::Mirror)),
             Tile::Joker(Joker::new(JokerVariant::Single)),
-            Tile::Basic(BasicTile::new(TileColor::Blue, 8)),
+            Tile::Basic(BasicTile::new(TileColor::Blue, 8).unwrap()),
             Tile::Joker(Joker::new(JokerVariant::ColorChange)),
-            Tile::Basic(BasicTile::new(TileColor::Black, 6)),
+            Tile::Basic(BasicTile::new(TileColor::Black, 6).unwrap()),
         ];
         assert_eq!(deserialize_set(input).unwrap(), expected);
     }
+
+    #[test]
+    fn test_deserialize_set_separators() {
+        let expected = deserialize_set("r1 r2 j").unwrap();
+        assert_eq!(deserialize_set("  r1,r2\t\n j ").unwrap(), expected);
+        assert_eq!(deserialize_set("r1, r2,,j").unwrap(), expected);
+        assert_eq!(deserialize_set("").unwrap(), vec![]);
+        assert_eq!(deserialize_set(" , ").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_deserialize_set_names_and_case() {
+        let expected = deserialize_set("r5 u12 a1 o13 c").unwrap();
+        let input = "Red5 blue 12 BLACK1 o 13 C";
+        assert_eq!(deserialize_set(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_deserialize_set_errors() {
+        assert_eq!(
+            deserialize_set("r1  j5 r3"),
+            Err(ParseError {
+                offset: 4,
+                token: "j5".to_string(),
+                kind: ParseErrorKind::UnknownTile,
+                suggestion: Some("j".to_string()),
+            })
+        );
+        let error = deserialize_set("r1 blue 14").unwrap_err();
+        assert_eq!(
+            (error.offset, error.kind),
+            (3, ParseErrorKind::InvalidValue)
+        );
+        assert_eq!(error.token, "blue 14");
+        let error = deserialize_set("r1 red").unwrap_err();
+        assert_eq!(
+            (error.offset, error.kind),
+            (3, ParseErrorKind::MissingValue)
+        );
+        let error = deserialize_set("bla7").unwrap_err();
+        assert_eq!(error.suggestion, Some("a7".to_string()));
+        assert_eq!(
+            error.to_string(),
+            "unrecognized token \"bla7\" at byte 0, did you mean \"a7\"?"
+        );
+        assert_eq!(deserialize_set("b7").unwrap_err().suggestion, None);
+        assert_eq!(
+            deserialize_set("r300").unwrap_err().kind,
+            ParseErrorKind::InvalidValue
+        );
+    }
+
+    #[test]
+    fn test_serialize_set() {
+        let set = deserialize_set("Red 5, BLACK6 j C").unwrap();
+        assert_eq!(serialize_set(&set), "r5 a6 j c");
+        assert_eq!(serialize_set(&[]), "");
+    }
+
+    #[test]
+    fn test_serialize_set_round_trip() {
+        let set: Vec<Tile> = TileId::all().map(TileId::tile).collect();
+        assert_eq!(deserialize_set(&serialize_set(&set)).unwrap(), set);
+        let set = deserialize_set("a6 c u8 u9 m j u8 c a6").unwrap();
+        assert_eq!(deserialize_set(&serialize_set(&set)).unwrap(), set);
+    }
+
+    #[test]
+    fn test_tile_from_str() {
+        for id in TileId::all() {
+            assert_eq!(id.tile().to_string().parse::<Tile>(), Ok(id.tile()));
+        }
+        assert_eq!(
+            " blue 12 ".parse::<Tile>(),
+            Ok(Tile::Basic(BasicTile::new(TileColor::Blue, 12).unwrap()))
+        );
+        let error = "r5 r6".parse::<Tile>().unwrap_err();
+        assert_eq!((error.offset, error.kind), (3, ParseErrorKind::NotOneTile));
+        assert_eq!(
+            "".parse::<Tile>().unwrap_err().kind,
+            ParseErrorKind::NotOneTile
+        );
+        assert_eq!(
+            "r0".parse::<Tile>().unwrap_err().kind,
+            ParseErrorKind::InvalidValue
+        );
+    }
+
+    #[test]
+    fn test_tile_set_display_and_from_str() {
+        let set: TileSet = "r1, r2, r3".parse().unwrap();
+        assert_eq!(set.to_string(), "r1 r2 r3");
+        assert_eq!(set.to_string().parse(), Ok(set.clone()));
+        assert_eq!(Vec::from(set), deserialize_set("r1 r2 r3").unwrap());
+    }
+
+    #[test]
+    fn test_deserialize_declared_set() {
+        let set = deserialize_declared_set("r5 J=Red6 r7 d=u4u5").unwrap();
+        assert_eq!(set.tiles, deserialize_set("r5 j r7 d").unwrap());
+        let tile = |color, value| BasicTile::new(color, value).unwrap();
+        let expected = vec![
+            (1, Declaration::Single(tile(TileColor::Red, 6))),
+            (
+                3,
+                Declaration::Double(tile(TileColor::Blue, 4), tile(TileColor::Blue, 5)),
+            ),
+        ];
+        assert_eq!(set.declarations, expected);
+        assert_eq!(set.declaration(3), Some(expected[1].1));
+        assert_eq!(set.declaration(0), None);
+        assert_eq!(deserialize_set("r5 j=r6 r7"), deserialize_set("r5 j r7"));
+    }
+
+    #[test]
+    fn test_declared_set_round_trip() {
+        let set: DeclaredSet = "a6 c d=u8u9 m j=r13 u8 c a6".parse().unwrap();
+        assert_eq!(set.to_string(), "a6 c d=u8u9 m j=r13 u8 c a6");
+        assert_eq!(set.to_string().parse(), Ok(set));
+    }
+
+    #[test]
+    fn test_invalid_declarations() {
+        for input in &[
+            "r5 j=r6u7",
+            "d=u4",
+            "r5=r6",
+            "c=r6",
+            "j=r14",
+            "j=",
+            "j=x6",
+            "j=6",
+        ] {
+            let error = deserialize_declared_set(input).unwrap_err();
+            assert_eq!(error.kind, ParseErrorKind::InvalidDeclaration, "{}", input);
+        }
+        let error = deserialize_declared_set("r5 d=u4").unwrap_err();
+        assert_eq!((error.offset, error.token.as_str()), (3, "d=u4"));
+    }
+
+    #[test]
+    fn test_invalid_tile_value() {
+        assert_eq!(
+            BasicTile::new(TileColor::Red, 0),
+            Err(InvalidTileValue { value: 0 })
+        );
+        assert_eq!(
+            BasicTile::new(TileColor::Red, 14),
+            Err(InvalidTileValue { value: 14 })
+        );
+    }
+
+    #[test]
+    fn test_bag_allows_two_copies() {
+        let board = vec![
+            deserialize_set("r5 r6 r7").unwrap(),
+            deserialize_set("r5 u5 a5 j").unwrap(),
+        ];
+        let rack = deserialize_set("j r6").unwrap();
+        assert_eq!(TileBag::standard().check_position(&board, &rack), Ok(()));
+    }
+
+    #[test]
+    fn test_bag_rejects_third_copy() {
+        let board = vec![
+            deserialize_set("r5 r6 r7").unwrap(),
+            deserialize_set("r5 u5 a5").unwrap(),
+        ];
+        let rack = deserialize_set("r5").unwrap();
+        assert_eq!(TileBag::standard().check_board(&board), Ok(()));
+
+        let expected = TooManyCopies {
+            tile: Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap()),
+            available: 2,
+        };
+        assert_eq!(
+            TileBag::standard().check_position(&board, &rack),
+            Err(expected)
+        );
+    }
+
+    #[test]
+    fn test_bag_rejects_special_jokers() {
+        let set = deserialize_set("r5 d r8").unwrap();
+        let expected = TooManyCopies {
+            tile: Tile::Joker(Joker::new(JokerVariant::Double)),
+            available: 0,
+        };
+        assert_eq!(TileBag::standard().check(&set), Err(expected));
+        assert_eq!(TileBag::special_jokers().check(&set), Ok(()));
+    }
+
+    #[test]
+    fn test_bag_rejects_invalid_values() {
+        for value in [0, 14] {
+            let tile = Tile::Basic(BasicTile {
+                color: TileColor::Black,
+                value,
+            });
+            let expected = TooManyCopies { tile, available: 0 };
+            assert_eq!(TileBag::standard().copies(&tile), 0);
+            assert_eq!(TileBag::standard().check(&[tile]), Err(expected));
+        }
+        // A black 14 is not counted as a third red 1.
+        let mut set = deserialize_set("r1 r1").unwrap();
+        set.insert(
+            1,
+            Tile::Basic(BasicTile {
+                color: TileColor::Black,
+                value: 14,
+            }),
+        );
+        let expected = TooManyCopies {
+            tile: set[1],
+            available: 0,
+        };
+        assert_eq!(TileBag::standard().check(&set), Err(expected));
+        assert_eq!(_slot(&set[1]), Err(InvalidTileValue { value: 14 }));
+    }
+
+    #[test]
+    fn test_bag_counts_past_255_copies() {
+        let bag = TileBag {
+            single_jokers: 255,
+            ..TileBag::standard()
+        };
+        let joker = Tile::Joker(Joker::new(JokerVariant::Single));
+        assert_eq!(bag.check(&vec![joker; 255]), Ok(()));
+        let expected = TooManyCopies {
+            tile: joker,
+            available: 255,
+        };
+        assert_eq!(bag.check(&vec![joker; 256]), Err(expected));
+    }
+
+    #[test]
+    fn test_rack_penalty() {
+        let rack = deserialize_set("r5 a13 j").unwrap();
+        assert_eq!(Penalties::standard().rack(&rack), 48);
+        assert_eq!(Penalties::standard().rack(&[]), 0);
+    }
+
+    #[test]
+    fn test_joker_penalties_per_variant() {
+        let penalties = Penalties {
+            double_joker: 50,
+            mirror_joker: 40,
+            ..Penalties::standard()
+        };
+        let rack = deserialize_set("r5 j d m c").unwrap();
+        assert_eq!(penalties.rack(&rack), 155);
+    }
+
+    #[test]
+    fn test_tile_ids_round_trip() {
+        for id in TileId::all() {
+            assert_eq!(TileId::new(&id.tile()), Ok(id));
+        }
+        assert_eq!(TileId::all().count(), TileId::COUNT);
+        assert_eq!(TileId::from_index(TileId::COUNT), None);
+    }
+
+    #[test]
+    fn test_tile_ids_order_like_tiles() {
+        let mut tiles = deserialize_set("c u3 j r13 a2 r1 o7").unwrap();
+        let mut ids: Vec<TileId> = tiles
+            .iter()
+            .map(|tile| TileId::new(tile).unwrap())
+            .collect();
+        tiles.sort();
+        ids.sort();
+        let sorted: Vec<Tile> = ids.into_iter().map(Tile::from).collect();
+        assert_eq!(sorted, tiles);
+        assert_eq!(sorted, deserialize_set("a2 r1 r13 u3 o7 j c").unwrap());
+    }
+
+    #[test]
+    fn test_multiset_ignores_order() {
+        let rack = TileMultiset::from_tiles(&deserialize_set("r5 j r5 a1").unwrap()).unwrap();
+        let other = TileMultiset::from_tiles(&deserialize_set("a1 r5 j r5").unwrap()).unwrap();
+        assert_eq!(rack, other);
+        assert_eq!(rack.len(), 4);
+        assert_eq!(rack.to_vec(), deserialize_set("a1 r5 r5 j").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_tiles_have_no_id() {
+        let zero = Tile::Basic(BasicTile {
+            color: TileColor::Orange,
+            value: 0,
+        });
+        let fourteen = Tile::Basic(BasicTile {
+            color: TileColor::Black,
+            value: 14,
+        });
+        assert_eq!(TileId::new(&zero), Err(InvalidTileValue { value: 0 }));
+        assert_eq!(
+            TileId::try_from(fourteen),
+            Err(InvalidTileValue { value: 14 })
+        );
+        let mut rack = TileMultiset::new();
+        assert_eq!(rack.insert(&fourteen), Err(InvalidTileValue { value: 14 }));
+        assert_eq!(rack.count(&deserialize_set("r1").unwrap()[0]), 0);
+        assert_eq!(rack.count(&zero), 0);
+        assert!(!rack.remove(&zero));
+        let tiles = vec![zero, fourteen];
+        assert_eq!(
+            TileMultiset::from_tiles(&tiles),
+            Err(InvalidTileValue { value: 0 })
+        );
+    }
+
+    #[test]
+    fn test_multiset_operations() {
+        let mut rack = TileMultiset::from_tiles(&deserialize_set("r5 r5 j").unwrap()).unwrap();
+        let played = TileMultiset::from_tiles(&deserialize_set("r5 j").unwrap()).unwrap();
+        assert!(played.is_subset(&rack));
+        assert_eq!(played.union(&played).intersection(&rack).len(), 3);
+        let left = rack.difference(&played).unwrap();
+        assert_eq!(left.to_vec(), deserialize_set("r5").unwrap());
+        assert_eq!(left.difference(&played), None);
+        let red = Tile::Basic(BasicTile::new(TileColor::Red, 5).unwrap());
+        assert!(rack.remove(&red));
+        assert!(rack.remove(&red));
+        assert!(!rack.remove(&red));
+        assert_eq!(rack.count(&red), 0);
+        assert!(!rack.is_empty());
+        assert!(TileMultiset::new().is_empty());
+    }
+
+    #[test]
+    fn test_multiset_keeps_at_most_255_copies() {
+        let joker = deserialize_set("j").unwrap()[0];
+        let mut rack = TileMultiset::new();
+        for _ in 0..300 {
+            rack.insert(&joker).unwrap();
+        }
+        assert_eq!(rack.count(&joker), 255);
+        assert_eq!(rack.union(&rack).count(&joker), 255);
+        assert_eq!(rack.len(), 255);
+    }
 }